@@ -5,9 +5,34 @@ use std::path::Path;
 
 fn main() {
     let dest = env::var("OUT_DIR").unwrap();
-    let mut file = File::create(&Path::new(&dest).join("bindings.rs")).unwrap();
+    let mut file = File::create(Path::new(&dest).join("bindings.rs")).unwrap();
 
-    Registry::new(Api::Gl, (4, 5), Profile::Core, Fallbacks::All, [])
-        .write_bindings(GlobalGenerator, &mut file)
-        .unwrap();
+    Registry::new(
+        Api::Gl,
+        (4, 5),
+        Profile::Core,
+        Fallbacks::All,
+        [
+            "GL_KHR_parallel_shader_compile",
+            "GL_ARB_parallel_shader_compile",
+        ],
+    )
+    .write_bindings(GlobalGenerator, &mut file)
+    .unwrap();
+
+    // the calls desktop gl has in its core profile but ES only as extensions
+    let mut file = File::create(Path::new(&dest).join("gles_bindings.rs")).unwrap();
+    Registry::new(
+        Api::Gles2,
+        (3, 0),
+        Profile::Core,
+        Fallbacks::All,
+        [
+            "GL_EXT_disjoint_timer_query",
+            "GL_EXT_tessellation_shader",
+            "GL_KHR_debug",
+        ],
+    )
+    .write_bindings(GlobalGenerator, &mut file)
+    .unwrap();
 }
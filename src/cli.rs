@@ -1,11 +1,14 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, str::FromStr};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(version, about)]
 pub struct Cli {
-    /// Path to the shader file
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to the shader file. `.wgsl` shaders are rendered with wgpu
     pub file: Option<PathBuf>,
 
     /// The amount of time (in milliseconds) under which consecutives file events are combined
@@ -15,6 +18,478 @@ pub struct Cli {
     /// Print all debug logs to the terminal
     #[arg(long, default_value_t = false)]
     pub debug: bool,
+
+    /// The OpenGL flavour of the context. With `es3`, shaders are translated
+    /// to GLSL ES unless `--glsl` says otherwise
+    #[arg(long, global = true, value_enum, default_value_t = GlApiArg::Gl)]
+    pub gl_api: GlApiArg,
+
+    /// The requested OpenGL (ES) version of the context, e.g. `4.5`.
+    /// Defaults to the newest the driver offers
+    #[arg(long, global = true)]
+    pub gl_version: Option<GlVersion>,
+
+    /// The profile of an OpenGL context. Ignored with `--gl-api es3`
+    #[arg(long, global = true, value_enum)]
+    pub gl_profile: Option<GlProfileArg>,
+
+    /// The robustness of the context against out of bounds accesses and
+    /// GPU resets
+    #[arg(long, global = true, value_enum, default_value_t = RobustnessArg::None)]
+    pub robustness: RobustnessArg,
+
+    /// Create a debug context and log the driver's messages, e.g. warnings
+    /// about shaders
+    #[arg(long, global = true, default_value_t = false)]
+    pub gl_debug: bool,
+
+    /// MSAA samples of the window's framebuffer (a power of two). Defaults to
+    /// the most the driver offers
+    #[arg(long, value_parser = parse_samples)]
+    pub samples: Option<u8>,
+
+    /// Initial size of the window in pixels, e.g. `1280x720`
+    #[arg(long)]
+    pub window_size: Option<Size>,
+
+    /// Initial position of the window's top left corner in pixels, e.g. `100,50`
+    #[arg(long)]
+    pub window_position: Option<Position>,
+
+    /// Start in borderless fullscreen (toggle at runtime with F11)
+    #[arg(long, default_value_t = false)]
+    pub fullscreen: bool,
+
+    /// Index of the monitor used for fullscreen. Defaults to the current monitor
+    #[arg(long)]
+    pub monitor: Option<usize>,
+
+    /// Keep the window above all other windows
+    #[arg(long, default_value_t = false)]
+    pub always_on_top: bool,
+
+    /// Synchronize buffer swaps with the monitor's refresh rate
+    #[arg(long, default_value_t = false)]
+    pub vsync: bool,
+
+    /// Render the shader at this fraction of the window resolution (0.25 - 2.0)
+    #[arg(long, default_value_t = 1.0, value_parser = parse_render_scale)]
+    pub render_scale: f32,
+
+    /// Render at a fixed resolution, e.g. `1080x1920`, letterboxed into the window.
+    /// Overrides `--render-scale`
+    #[arg(long)]
+    pub resolution: Option<Size>,
+
+    /// The filter used to upscale the rendered image to the window
+    #[arg(long, value_enum, default_value_t = ScaleFilter::Linear)]
+    pub scale_filter: ScaleFilter,
+
+    /// A second shader to compare against (cycle the view with C)
+    #[arg(long, conflicts_with = "compare_rev")]
+    pub compare: Option<PathBuf>,
+
+    /// Compare against a git revision of the shader file, e.g. `HEAD`
+    #[arg(long, requires = "file")]
+    pub compare_rev: Option<String>,
+
+    /// A vertex shader replacing the built-in full screen one
+    #[arg(long)]
+    pub vertex: Option<PathBuf>,
+
+    /// A tessellation control shader
+    #[arg(long)]
+    pub tess_control: Option<PathBuf>,
+
+    /// A tessellation evaluation shader. Meshes are drawn as patches of 3
+    /// vertices, the full screen quad as one patch of 4
+    #[arg(long = "tess-eval")]
+    pub tess_evaluation: Option<PathBuf>,
+
+    /// A geometry shader
+    #[arg(long)]
+    pub geometry: Option<PathBuf>,
+
+    /// An OBJ or glTF file to draw the shader onto (drag to orbit, scroll to zoom)
+    #[arg(long)]
+    pub mesh: Option<PathBuf>,
+
+    /// Draw this many vertices without a vertex buffer. The `--vertex` shader
+    /// computes their positions from `gl_VertexID`
+    #[arg(long, requires = "vertex", conflicts_with = "mesh")]
+    pub vertex_count: Option<u32>,
+
+    /// The primitive procedural vertices are assembled into
+    #[arg(long, value_enum, default_value_t = PrimitiveType::Points, requires = "vertex_count")]
+    pub primitive: PrimitiveType,
+
+    /// A fixed point size in pixels. Otherwise the vertex shader may set `gl_PointSize`
+    #[arg(long, requires = "vertex_count")]
+    pub point_size: Option<f32>,
+
+    /// A compute shader dispatched before every frame. Can be repeated, the
+    /// passes run in order (reset their buffers and images with R)
+    #[arg(long)]
+    pub compute: Vec<PathBuf>,
+
+    /// Define a macro for the shader and its stages, e.g. `-D STEPS=64`. The
+    /// value defaults to `1`. Overrides the `[defines]` of the shader's
+    /// `<name>.toml` manifest
+    #[arg(short = 'D', long = "define", value_name = "NAME[=VALUE]")]
+    pub defines: Vec<Define>,
+
+    /// Translate shaders to this GLSL version. By default shaders keep their
+    /// `#version`, and shaders without one are compiled as GLSL 330 core
+    #[arg(long, value_enum)]
+    pub glsl: Option<GlslVersion>,
+
+    /// Always compile shaders instead of reusing cached program binaries
+    #[arg(long, default_value_t = false)]
+    pub no_program_cache: bool,
+
+    /// Disable depth testing when drawing a mesh or procedural vertices
+    #[arg(long, default_value_t = false)]
+    pub no_depth_test: bool,
+
+    /// Which faces of a mesh are culled
+    #[arg(long, value_enum, default_value_t = CullMode::None)]
+    pub cull: CullMode,
+
+    /// Record the input of this session into a file, which `--replay` plays back
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Replay a recorded session headlessly, with the same shader and
+    /// options, and save every frame into `--replay-output`
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+
+    /// The directory the frames of `--replay` are saved into
+    #[arg(long, default_value = "replay", requires = "replay")]
+    pub replay_output: PathBuf,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render two shaders headlessly with identical inputs and compare the results
+    Diff(DiffArgs),
+    /// Render a shader headlessly for a number of frames and report the frame times
+    Bench(BenchArgs),
+    /// Parse and validate shaders without a gl context or display
+    Check(CheckArgs),
+    /// Translate a shader to another shading language
+    Convert(ConvertArgs),
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// The reference shader. `.wgsl` shaders are rendered with wgpu
+    pub a: PathBuf,
+
+    /// The shader compared against the reference
+    pub b: PathBuf,
+
+    /// The value of `uTime` in seconds
+    #[arg(long, default_value_t = 0.0)]
+    pub time: f32,
+
+    /// The resolution both shaders are rendered at
+    #[arg(long, default_value = "512x512")]
+    pub size: Size,
+
+    /// The value of `uMouse` in pixels, measured from the bottom left, e.g. `256,256`
+    #[arg(long)]
+    pub mouse: Option<Position>,
+
+    /// Where the difference heatmap is written
+    #[arg(long, default_value = "diff.png")]
+    pub output: PathBuf,
+
+    /// The factor the absolute difference is multiplied by in the heatmap
+    #[arg(long, default_value_t = 16.0)]
+    pub gain: f32,
+
+    /// Fail if the PSNR is below this value (in dB)
+    #[arg(long)]
+    pub min_psnr: Option<f64>,
+
+    /// Fail if any channel differs by more than this many 8-bit levels
+    #[arg(long)]
+    pub max_error: Option<u8>,
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// The shader to benchmark
+    pub shader: PathBuf,
+
+    /// The resolution the shader is rendered at
+    #[arg(long, default_value = "1920x1080")]
+    pub size: Size,
+
+    /// The number of measured frames
+    #[arg(long, default_value_t = 500, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frames: u32,
+
+    /// The number of frames rendered before measuring, which are discarded
+    #[arg(long, default_value_t = 50)]
+    pub warmup: u32,
+
+    /// How far `uTime` advances every frame, in seconds
+    #[arg(long, default_value_t = 1.0 / 60.0)]
+    pub time_step: f32,
+
+    /// The format of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+
+    /// Write the report to a file instead of the terminal
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// A JSON report of an earlier run to compare against
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+
+    /// Fail if the median GPU time is this many percent slower than the baseline
+    #[arg(long, default_value_t = 5.0)]
+    pub threshold: f64,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// The fragment shaders to validate, GLSL or WGSL
+    #[arg(required = true)]
+    pub shaders: Vec<PathBuf>,
+
+    /// Define a macro for GLSL shaders, e.g. `-D STEPS=64`. The value defaults to `1`.
+    /// Overrides the `[defines]` of the shader's manifest
+    #[arg(short = 'D', long = "define", value_name = "NAME[=VALUE]")]
+    pub defines: Vec<Define>,
+}
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// The fragment shader to translate, GLSL or WGSL
+    pub input: PathBuf,
+
+    /// The shading language to translate to
+    #[arg(long, value_enum)]
+    pub to: ConvertTarget,
+
+    /// Write the result to a file instead of the terminal. Required for SPIR-V
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Define a macro for GLSL shaders, e.g. `-D STEPS=64`. The value defaults to `1`.
+    /// Overrides the `[defines]` of the shader's manifest
+    #[arg(short = 'D', long = "define", value_name = "NAME[=VALUE]")]
+    pub defines: Vec<Define>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ConvertTarget {
+    Wgsl,
+    Spirv,
+    Msl,
+    Hlsl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScaleFilter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PrimitiveType {
+    Points,
+    Lines,
+    LineStrip,
+    Triangles,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GlApiArg {
+    Gl,
+    Es3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GlProfileArg {
+    Core,
+    Compat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RobustnessArg {
+    None,
+    /// Out of bounds accesses are undefined behavior, and gl errors are not
+    /// reported
+    NoError,
+    /// Out of bounds accesses are safe, but resets are not reported
+    NoResetNotification,
+    /// Out of bounds accesses are safe, and the context is lost on a reset
+    LoseContextOnReset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GlslVersion {
+    #[value(name = "330")]
+    Glsl330,
+    #[value(name = "450")]
+    Glsl450,
+    #[value(name = "300es")]
+    Es300,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+/// A size in pixels, parsed from `<width>x<height>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Size {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Size {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected `<width>x<height>`, got `{}`", s))?;
+        let width = width
+            .trim()
+            .parse::<u32>()
+            .map_err(|err| format!("invalid width `{}`: {}", width, err))?;
+        let height = height
+            .trim()
+            .parse::<u32>()
+            .map_err(|err| format!("invalid height `{}`: {}", height, err))?;
+        if width == 0 || height == 0 {
+            return Err("width and height must be greater than zero".to_string());
+        }
+        Ok(Self { width, height })
+    }
+}
+
+/// A position in pixels, parsed from `<x>,<y>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (x, y) = s
+            .split_once(',')
+            .ok_or_else(|| format!("expected `<x>,<y>`, got `{}`", s))?;
+        let x = x
+            .trim()
+            .parse::<i32>()
+            .map_err(|err| format!("invalid x `{}`: {}", x, err))?;
+        let y = y
+            .trim()
+            .parse::<i32>()
+            .map_err(|err| format!("invalid y `{}`: {}", y, err))?;
+        Ok(Self { x, y })
+    }
+}
+
+/// A gl version, parsed from `<major>.<minor>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl FromStr for GlVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (major, minor) = s
+            .split_once('.')
+            .ok_or_else(|| format!("expected `<major>.<minor>`, got `{}`", s))?;
+        let major = major
+            .trim()
+            .parse::<u8>()
+            .map_err(|err| format!("invalid major version `{}`: {}", major, err))?;
+        let minor = minor
+            .trim()
+            .parse::<u8>()
+            .map_err(|err| format!("invalid minor version `{}`: {}", minor, err))?;
+        Ok(Self { major, minor })
+    }
+}
+
+/// A macro definition, parsed from `<name>[=<value>]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Define {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for Define {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once('=').unwrap_or((s, "1"));
+        let name = name.trim();
+        let is_identifier = name
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !is_identifier {
+            return Err(format!("invalid macro name `{}`", name));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+fn parse_render_scale(s: &str) -> Result<f32, String> {
+    let scale = s
+        .parse::<f32>()
+        .map_err(|err| format!("invalid render scale `{}`: {}", s, err))?;
+    if (0.25..=2.0).contains(&scale) {
+        Ok(scale)
+    } else {
+        Err(format!(
+            "render scale must be between 0.25 and 2.0, got {}",
+            scale
+        ))
+    }
+}
+
+fn parse_samples(s: &str) -> Result<u8, String> {
+    let samples = s
+        .parse::<u8>()
+        .map_err(|err| format!("invalid sample count `{}`: {}", s, err))?;
+    if samples.is_power_of_two() {
+        Ok(samples)
+    } else {
+        Err(format!(
+            "the sample count must be a power of two, got {}",
+            samples
+        ))
+    }
 }
-// #[command(subcommand)]
-// pub command: Option<Commands>,
@@ -0,0 +1,339 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{bail, Context};
+use log::info;
+use serde::{Deserialize, Serialize};
+use shader_playground::{
+    cli_support::{is_wgsl, Format, OffscreenShader, RenderTarget},
+    compat, gl, ContextOptions, Filter, FrameInputs, HeadlessContext, PlaneBuffer,
+};
+
+use crate::cli::{BenchArgs, ReportFormat};
+
+/// Summary statistics of a series of frame times, in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub mean: f64,
+    pub median: f64,
+    pub stddev: f64,
+    pub p95: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    pub fn new(samples: &[f64]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let n = sorted.len().max(1) as f64;
+
+        let mean = sorted.iter().sum::<f64>() / n;
+        let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+        let median = match sorted.len() {
+            0 => 0.0,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+            len => sorted[len / 2],
+        };
+        // nearest rank
+        let p95 = sorted
+            .get(((0.95 * n).ceil() as usize).saturating_sub(1))
+            .copied()
+            .unwrap_or(0.0);
+
+        Self {
+            mean,
+            median,
+            stddev: variance.sqrt(),
+            p95,
+            min: sorted.first().copied().unwrap_or(0.0),
+            max: sorted.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub shader: PathBuf,
+    pub resolution: (u32, u32),
+    pub frames: u32,
+    pub warmup: u32,
+    /// The time the GPU spent drawing, measured with timer queries.
+    pub gpu_ms: Stats,
+    /// The wall clock time from submitting a frame until `glFinish` returns.
+    pub cpu_ms: Stats,
+}
+
+impl BenchReport {
+    fn format(&self, format: ReportFormat) -> anyhow::Result<String> {
+        let mut out = String::new();
+        let rows = [("gpu", self.gpu_ms), ("cpu", self.cpu_ms)];
+        match format {
+            ReportFormat::Text => {
+                writeln!(
+                    out,
+                    "`{}` at {}x{}, {} frames ({} warm-up)",
+                    self.shader.display(),
+                    self.resolution.0,
+                    self.resolution.1,
+                    self.frames,
+                    self.warmup
+                )?;
+                writeln!(
+                    out,
+                    "{:<4} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+                    "ms", "mean", "median", "stddev", "p95", "min", "max"
+                )?;
+                for (name, s) in rows {
+                    writeln!(
+                        out,
+                        "{:<4} {:>9.4} {:>9.4} {:>9.4} {:>9.4} {:>9.4} {:>9.4}",
+                        name, s.mean, s.median, s.stddev, s.p95, s.min, s.max
+                    )?;
+                }
+            }
+            ReportFormat::Json => {
+                out = serde_json::to_string_pretty(self).with_context(|| "serializing report")?;
+                out.push('\n');
+            }
+            ReportFormat::Csv => {
+                writeln!(
+                    out,
+                    "timer,mean_ms,median_ms,stddev_ms,p95_ms,min_ms,max_ms"
+                )?;
+                for (name, s) in rows {
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{},{}",
+                        name, s.mean, s.median, s.stddev, s.p95, s.min, s.max
+                    )?;
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// A `GL_TIME_ELAPSED` query.
+struct GpuTimer {
+    query: u32,
+}
+
+impl GpuTimer {
+    fn new() -> Self {
+        let mut query = 0;
+        unsafe {
+            gl::GenQueries(1, &mut query);
+        }
+        Self { query }
+    }
+
+    fn begin(&self) {
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, self.query);
+        }
+    }
+
+    fn end(&self) {
+        unsafe {
+            gl::EndQuery(gl::TIME_ELAPSED);
+        }
+    }
+
+    /// Waits for the result, in milliseconds.
+    fn elapsed_ms(&self) -> f64 {
+        compat::query_result_u64(self.query) as f64 / 1e6
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteQueries(1, &self.query);
+        }
+    }
+}
+
+pub fn run(args: BenchArgs, context: ContextOptions) -> anyhow::Result<()> {
+    if is_wgsl(&args.shader) {
+        bail!("benchmarking uses gl timer queries, so WGSL shaders are not supported");
+    }
+    let _context =
+        HeadlessContext::new(context).with_context(|| "creating a headless gl context")?;
+    if !compat::has_timer_queries() {
+        bail!("GPU timer queries need `GL_EXT_disjoint_timer_query` on OpenGL ES");
+    }
+    let plane = PlaneBuffer::new().with_context(|| "creating plane buffer")?;
+
+    let resolution = (args.size.width, args.size.height);
+    let shader = OffscreenShader::from_file(&args.shader)?;
+    let target = RenderTarget::new(resolution.0, resolution.1, Format::Rgba8, Filter::Nearest)?;
+    let timer = GpuTimer::new();
+
+    info!(
+        "rendering `{}` at {}x{}: {} warm-up and {} measured frames",
+        args.shader.display(),
+        resolution.0,
+        resolution.1,
+        args.warmup,
+        args.frames
+    );
+
+    let mut gpu_ms = Vec::with_capacity(args.frames as usize);
+    let mut cpu_ms = Vec::with_capacity(args.frames as usize);
+    for frame in 0..args.warmup + args.frames {
+        let inputs = FrameInputs {
+            resolution,
+            time: frame as f32 * args.time_step,
+            mouse: (0.0, 0.0),
+        };
+
+        let start = Instant::now();
+        timer.begin();
+        shader.draw(&target, &plane, inputs);
+        timer.end();
+        // every frame is measured in isolation
+        unsafe {
+            gl::Finish();
+        }
+        let cpu = start.elapsed().as_secs_f64() * 1e3;
+
+        if frame >= args.warmup {
+            gpu_ms.push(timer.elapsed_ms());
+            cpu_ms.push(cpu);
+        }
+    }
+
+    let report = BenchReport {
+        shader: args.shader.clone(),
+        resolution,
+        frames: args.frames,
+        warmup: args.warmup,
+        gpu_ms: Stats::new(&gpu_ms),
+        cpu_ms: Stats::new(&cpu_ms),
+    };
+
+    let formatted = report.format(args.format)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, formatted)
+            .with_context(|| format!("writing `{}`", path.display()))?,
+        None => print!("{}", formatted),
+    }
+
+    if let Some(path) = &args.baseline {
+        check_regression(&report, path, args.threshold)?;
+    }
+
+    Ok(())
+}
+
+/// Fails if the median GPU time regressed by more than `threshold` percent.
+fn check_regression(report: &BenchReport, baseline: &Path, threshold: f64) -> anyhow::Result<()> {
+    let json = std::fs::read_to_string(baseline)
+        .with_context(|| format!("reading `{}`", baseline.display()))?;
+    let baseline: BenchReport = serde_json::from_str(&json)
+        .with_context(|| "parsing the baseline, expected a JSON report")?;
+    compare_to_baseline(report, &baseline, threshold)
+}
+
+fn compare_to_baseline(
+    report: &BenchReport,
+    baseline: &BenchReport,
+    threshold: f64,
+) -> anyhow::Result<()> {
+    if baseline.resolution != report.resolution {
+        bail!(
+            "the baseline was measured at {}x{}, not {}x{}",
+            baseline.resolution.0,
+            baseline.resolution.1,
+            report.resolution.0,
+            report.resolution.1
+        );
+    }
+
+    // a zero median makes the change infinite or NaN, which never compares
+    if baseline.gpu_ms.median.is_nan() || baseline.gpu_ms.median <= 0.0 {
+        bail!(
+            "the baseline median gpu time is {} ms, expected a positive time",
+            baseline.gpu_ms.median
+        );
+    }
+
+    let change = 100.0 * (report.gpu_ms.median / baseline.gpu_ms.median - 1.0);
+    info!(
+        "median gpu time {:.4} ms, baseline {:.4} ms ({:+.2}%)",
+        report.gpu_ms.median, baseline.gpu_ms.median, change
+    );
+    if change > threshold {
+        bail!(
+            "median gpu time regressed by {:.2}% (threshold {:.2}%)",
+            change,
+            threshold
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(resolution: (u32, u32), median: f64) -> BenchReport {
+        let stats = Stats::new(&[median]);
+        BenchReport {
+            shader: PathBuf::from("shader.glsl"),
+            resolution,
+            frames: 1,
+            warmup: 0,
+            gpu_ms: stats,
+            cpu_ms: stats,
+        }
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(Stats::new(&[3.0, 1.0, 2.0]).median, 2.0);
+        assert_eq!(Stats::new(&[4.0, 1.0, 3.0, 2.0]).median, 2.5);
+        assert_eq!(Stats::new(&[]).median, 0.0);
+    }
+
+    #[test]
+    fn p95_is_the_nearest_rank() {
+        let samples = (1..=100).map(f64::from).rev().collect::<Vec<_>>();
+        let stats = Stats::new(&samples);
+        assert_eq!(stats.p95, 95.0);
+        assert_eq!((stats.min, stats.max), (1.0, 100.0));
+        assert_eq!(stats.mean, 50.5);
+
+        assert_eq!(Stats::new(&[1.0, 2.0, 3.0]).p95, 3.0);
+        assert_eq!(Stats::new(&[7.0]).p95, 7.0);
+    }
+
+    #[test]
+    fn stddev_is_the_sample_deviation() {
+        let stats = Stats::new(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert!((stats.stddev - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert_eq!(Stats::new(&[7.0]).stddev, 0.0);
+    }
+
+    #[test]
+    fn detects_regressions() {
+        let baseline = report((64, 64), 2.0);
+        assert!(compare_to_baseline(&report((64, 64), 2.1), &baseline, 10.0).is_ok());
+        assert!(compare_to_baseline(&report((64, 64), 2.4), &baseline, 10.0).is_err());
+        assert!(compare_to_baseline(&report((32, 32), 2.0), &baseline, 10.0).is_err());
+    }
+
+    #[test]
+    fn rejects_a_zero_baseline() {
+        let baseline = report((64, 64), 0.0);
+        let err = compare_to_baseline(&report((64, 64), 1.0), &baseline, 10.0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the baseline median gpu time is 0 ms, expected a positive time"
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use anyhow::bail;
+use shader_playground::cli_support::validation;
+
+use crate::cli::CheckArgs;
+
+pub fn run(args: CheckArgs) -> anyhow::Result<()> {
+    let defines = args
+        .defines
+        .into_iter()
+        .map(|define| (define.name, define.value))
+        .collect::<Vec<_>>();
+
+    let mut failed = 0;
+    for path in &args.shaders {
+        match validation::validate_file(path, &defines) {
+            Ok(_) => println!("{}: ok", path.display()),
+            Err(err) => {
+                eprintln!("{:#}\n", err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} of {} shaders are invalid", failed, args.shaders.len());
+    }
+    Ok(())
+}
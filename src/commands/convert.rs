@@ -0,0 +1,69 @@
+use anyhow::{bail, Context};
+use log::info;
+use naga::{back, ShaderStage};
+use shader_playground::cli_support::validation;
+
+use crate::cli::{ConvertArgs, ConvertTarget};
+
+pub fn run(args: ConvertArgs) -> anyhow::Result<()> {
+    let defines = args
+        .defines
+        .into_iter()
+        .map(|define| (define.name, define.value))
+        .collect::<Vec<_>>();
+    let shader = validation::validate_file(&args.input, &defines)?;
+    let (module, info) = (&shader.module, &shader.info);
+
+    let output = match args.to {
+        ConvertTarget::Wgsl => {
+            back::wgsl::write_string(module, info, back::wgsl::WriterFlags::empty())
+                .with_context(|| "writing WGSL")?
+                .into_bytes()
+        }
+        ConvertTarget::Spirv => {
+            let pipeline_options = back::spv::PipelineOptions {
+                shader_stage: ShaderStage::Fragment,
+                entry_point: shader.entry_point().to_string(),
+            };
+            back::spv::write_vec(
+                module,
+                info,
+                &back::spv::Options::default(),
+                Some(&pipeline_options),
+            )
+            .with_context(|| "writing SPIR-V")?
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect()
+        }
+        ConvertTarget::Msl => back::msl::write_string(
+            module,
+            info,
+            &back::msl::Options::default(),
+            &back::msl::PipelineOptions::default(),
+        )
+        .with_context(|| "writing MSL")?
+        .0
+        .into_bytes(),
+        ConvertTarget::Hlsl => {
+            let mut hlsl = String::new();
+            back::hlsl::Writer::new(&mut hlsl, &back::hlsl::Options::default())
+                .write(module, info, None)
+                .with_context(|| "writing HLSL")?;
+            hlsl.into_bytes()
+        }
+    };
+
+    match args.output {
+        Some(path) => {
+            std::fs::write(&path, output)
+                .with_context(|| format!("writing `{}`", path.display()))?;
+            info!("wrote `{}`", path.display());
+        }
+        None if args.to == ConvertTarget::Spirv => {
+            bail!("SPIR-V is a binary format, pass `--output <FILE>`")
+        }
+        None => print!("{}", String::from_utf8_lossy(&output)),
+    }
+    Ok(())
+}
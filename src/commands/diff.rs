@@ -0,0 +1,245 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use log::info;
+use shader_playground::{ContextOptions, FrameInputs, HeadlessRenderer, Image};
+
+use crate::cli::DiffArgs;
+
+/// The size of the square windows SSIM is computed over.
+const SSIM_WINDOW: usize = 8;
+const SSIM_STRIDE: usize = 4;
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+/// How similar two renders are. The errors are measured over the RGB channels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffReport {
+    /// Infinite if the images are identical.
+    pub psnr: f64,
+    /// The mean structural similarity of the luma, from -1 to 1 (identical).
+    pub ssim: f64,
+    /// The largest difference of any channel, in 8-bit levels.
+    pub max_error: u8,
+    pub differing_pixels: usize,
+}
+
+pub fn run(args: DiffArgs, context: ContextOptions) -> anyhow::Result<()> {
+    let inputs = FrameInputs {
+        resolution: (args.size.width, args.size.height),
+        time: args.time,
+        mouse: args
+            .mouse
+            .map(|mouse| (mouse.x as f32, mouse.y as f32))
+            .unwrap_or_default(),
+    };
+    let render = |path: &Path| HeadlessRenderer::render_file(path, context, inputs);
+    let a = render(&args.a)?;
+    let b = render(&args.b)?;
+
+    let report = diff(&a, &b)?;
+    let pixels = a.width as usize * a.height as usize;
+    println!("PSNR:      {:.2} dB", report.psnr);
+    println!("SSIM:      {:.5}", report.ssim);
+    println!(
+        "max error: {}/255 ({:.4})",
+        report.max_error,
+        report.max_error as f64 / 255.0
+    );
+    println!(
+        "differing: {} pixels ({:.3}%)",
+        report.differing_pixels,
+        100.0 * report.differing_pixels as f64 / pixels as f64
+    );
+
+    heatmap(&a, &b, args.gain)
+        .save_png(&args.output, &[])
+        .with_context(|| "saving the difference heatmap")?;
+    info!(
+        "saved the difference heatmap to `{}`",
+        args.output.display()
+    );
+
+    if let Some(min_psnr) = args.min_psnr {
+        if report.psnr < min_psnr {
+            bail!("PSNR {:.2} dB is below {:.2} dB", report.psnr, min_psnr);
+        }
+    }
+    if let Some(max_error) = args.max_error {
+        if report.max_error > max_error {
+            bail!("max error {} exceeds {}", report.max_error, max_error);
+        }
+    }
+
+    Ok(())
+}
+
+/// Compares two images, which must have the same size.
+pub fn diff(a: &Image, b: &Image) -> anyhow::Result<DiffReport> {
+    if (a.width, a.height) != (b.width, b.height) {
+        bail!(
+            "the images have different sizes: {}x{} and {}x{}",
+            a.width,
+            a.height,
+            b.width,
+            b.height
+        );
+    }
+
+    let mut squared_error = 0.0;
+    let mut max_error = 0;
+    let mut differing_pixels = 0;
+    for (a, b) in a.pixels.chunks_exact(4).zip(b.pixels.chunks_exact(4)) {
+        let mut differs = false;
+        for channel in 0..3 {
+            let error = a[channel].abs_diff(b[channel]);
+            squared_error += (error as f64 / 255.0).powi(2);
+            max_error = max_error.max(error);
+            differs |= error != 0;
+        }
+        differing_pixels += differs as usize;
+    }
+
+    let mse = squared_error / (a.pixels.len() / 4 * 3).max(1) as f64;
+    Ok(DiffReport {
+        psnr: 10.0 * (1.0 / mse).log10(),
+        ssim: ssim(&luma(a), &luma(b), a.width as usize, a.height as usize),
+        max_error,
+        differing_pixels,
+    })
+}
+
+fn luma(image: &Image) -> Vec<f64> {
+    image
+        .pixels
+        .chunks_exact(4)
+        .map(|p| (0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64) / 255.0)
+        .collect()
+}
+
+/// The mean SSIM over `SSIM_WINDOW` sized windows, `SSIM_STRIDE` pixels apart.
+/// Images smaller than a window are treated as a single window.
+fn ssim(a: &[f64], b: &[f64], width: usize, height: usize) -> f64 {
+    let window = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+
+    let mut sum = 0.0;
+    let mut count = 0;
+    for y in (0..=height - window.1).step_by(SSIM_STRIDE) {
+        for x in (0..=width - window.0).step_by(SSIM_STRIDE) {
+            let pixels = (y..y + window.1)
+                .flat_map(|y| (x..x + window.0).map(move |x| y * width + x))
+                .map(|i| (a[i], b[i]));
+            let n = (window.0 * window.1) as f64;
+
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            for (a, b) in pixels {
+                sum_a += a;
+                sum_b += b;
+                sum_aa += a * a;
+                sum_bb += b * b;
+                sum_ab += a * b;
+            }
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let variance_a = sum_aa / n - mean_a * mean_a;
+            let variance_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+
+            sum += ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1)
+                    * (variance_a + variance_b + SSIM_C2));
+            count += 1;
+        }
+    }
+    sum / count.max(1) as f64
+}
+
+/// The largest channel difference of every pixel times `gain`, from black
+/// through red and yellow to white.
+fn heatmap(a: &Image, b: &Image, gain: f32) -> Image {
+    let pixels = a
+        .pixels
+        .chunks_exact(4)
+        .zip(b.pixels.chunks_exact(4))
+        .flat_map(|(a, b)| {
+            let error = (0..3).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0);
+            let t = (error as f32 / 255.0 * gain).min(1.0);
+            let ramp = |offset: f32| ((3.0 * t - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+            [ramp(0.0), ramp(1.0), ramp(2.0), 255]
+        })
+        .collect();
+
+    Image {
+        width: a.width,
+        height: a.height,
+        pixels,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `width`x`height` image with a different gray level in every pixel.
+    fn gradient(width: u32, height: u32) -> Image {
+        let pixels = (0..width * height)
+            .flat_map(|i| {
+                let v = (i * 7 % 256) as u8;
+                [v, v, v, 255]
+            })
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let image = gradient(16, 12);
+        let report = diff(&image, &image).unwrap();
+        assert_eq!(report.psnr, f64::INFINITY);
+        assert!((report.ssim - 1.0).abs() < 1e-9, "ssim = {}", report.ssim);
+        assert_eq!(report.max_error, 0);
+        assert_eq!(report.differing_pixels, 0);
+    }
+
+    #[test]
+    fn measures_a_single_pixel_error() {
+        let a = gradient(16, 12);
+        let mut b = gradient(16, 12);
+        b.pixels[4 * 20 + 1] = b.pixels[4 * 20 + 1].wrapping_add(40);
+        // alpha is not compared
+        b.pixels[4 * 30 + 3] = 0;
+
+        let report = diff(&a, &b).unwrap();
+        assert_eq!(report.max_error, 40);
+        assert_eq!(report.differing_pixels, 1);
+        let mse = (40.0f64 / 255.0).powi(2) / (16.0 * 12.0 * 3.0);
+        assert!((report.psnr - 10.0 * (1.0 / mse).log10()).abs() < 1e-9);
+        assert!(report.ssim < 1.0);
+    }
+
+    #[test]
+    fn rejects_different_sizes() {
+        let err = diff(&gradient(16, 12), &gradient(12, 16)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the images have different sizes: 16x12 and 12x16"
+        );
+    }
+
+    #[test]
+    fn heatmap_ramps_with_the_error() {
+        let a = Image::black(3, 1);
+        let mut b = Image::black(3, 1);
+        b.pixels[4] = 85;
+        b.pixels[8] = 255;
+        let heatmap = heatmap(&a, &b, 1.0);
+        assert_eq!(
+            heatmap.pixels,
+            [0, 0, 0, 255, 255, 0, 0, 255, 255, 255, 255, 255]
+        );
+    }
+}
@@ -0,0 +1,5 @@
+pub mod bench;
+pub mod check;
+pub mod convert;
+pub mod diff;
+pub mod replay;
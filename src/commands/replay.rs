@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use log::info;
+use shader_playground::{
+    cli_support::source_hash, ContextOptions, Header, HeadlessHost, Session, ShaderPlayground,
+    ShaderPlaygroundArgs,
+};
+
+/// Options that only change the window or the logs, not what is drawn, and
+/// whether they take a value.
+const WINDOW_OPTIONS: [(&str, bool); 10] = [
+    ("--debug", false),
+    ("--window-size", true),
+    ("--window-position", true),
+    ("--fullscreen", false),
+    ("--monitor", true),
+    ("--always-on-top", false),
+    ("--vsync", false),
+    ("--record", true),
+    ("--replay", true),
+    ("--replay-output", true),
+];
+
+/// The command line `args` without the program name and the options that do
+/// not change what is drawn, to check that a session is replayed with the
+/// options it was recorded with.
+pub fn options(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut args = args.into_iter().skip(1);
+    let mut options = Vec::new();
+    while let Some(arg) = args.next() {
+        let name = arg.split_once('=').map_or(arg.as_str(), |(name, _)| name);
+        match WINDOW_OPTIONS.iter().find(|(option, _)| *option == name) {
+            Some((_, true)) if !arg.contains('=') => {
+                args.next();
+            }
+            Some(_) => {}
+            None => options.push(arg),
+        }
+    }
+    options
+}
+
+/// The header of a session recorded with `args` and the command line
+/// `options`. The size is filled in by the recorder.
+pub fn header(args: &ShaderPlaygroundArgs, options: Vec<String>) -> Header {
+    let source = args
+        .file
+        .as_ref()
+        .and_then(|path| std::fs::read_to_string(path).ok());
+    Header {
+        shader: args.file.clone(),
+        source_sha256: source.as_deref().map(source_hash),
+        size: (0, 0),
+        options,
+    }
+}
+
+/// Replays the session at `path` in a headless playground created with
+/// `args`, saving every frame into `output` as `frame_00000.png` and so on.
+/// `options` must match the ones the session was recorded with.
+pub fn run(
+    path: &Path,
+    output: &Path,
+    args: ShaderPlaygroundArgs,
+    options: Vec<String>,
+    context: ContextOptions,
+) -> anyhow::Result<()> {
+    let session = Session::load(path)?;
+    let recorded = &session.header;
+    let expected = header(&args, options);
+    if recorded.options != expected.options {
+        bail!(
+            "`{}` was recorded with other options, replay it with `{}`",
+            path.display(),
+            recorded.options.join(" ")
+        );
+    }
+    if recorded.source_sha256 != expected.source_sha256 {
+        let shader = recorded
+            .shader
+            .as_deref()
+            .unwrap_or(Path::new("the shader"));
+        bail!(
+            "`{}` changed since `{}` was recorded",
+            shader.display(),
+            path.display()
+        );
+    }
+    let size = recorded.size;
+    if size.0 == 0 || size.1 == 0 {
+        bail!("`{}` does not record a window size", path.display());
+    }
+    std::fs::create_dir_all(output).with_context(|| format!("creating `{}`", output.display()))?;
+
+    let mut host = HeadlessHost::new(context, size, ShaderPlayground::new, args)?;
+    let frames = session.frames();
+    info!("replaying {} frames at {}x{}", frames, size.0, size.1);
+    let mut saved = 0;
+    session.replay(&mut host, |frame, image| {
+        saved += 1;
+        image.save_png(&output.join(format!("frame_{:05}.png", frame)), &[])
+    })?;
+
+    info!("saved {} frames to `{}`", saved, output.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn keeps_the_options_that_change_the_frames() {
+        assert_eq!(
+            options(args(
+                "shader-playground shader.glsl -D A=1 --compute sim.comp"
+            )),
+            args("shader.glsl -D A=1 --compute sim.comp")
+        );
+    }
+
+    #[test]
+    fn skips_window_options() {
+        let recorded = options(args(
+            "shader-playground --record s.jsonl --vsync shader.glsl --window-size 800x600 --glsl 450",
+        ));
+        let replayed = options(args(
+            "shader-playground --replay=s.jsonl shader.glsl --replay-output out --debug --glsl 450",
+        ));
+        assert_eq!(recorded, args("shader.glsl --glsl 450"));
+        assert_eq!(replayed, recorded);
+    }
+}
@@ -0,0 +1,196 @@
+//! The gl calls that differ between desktop OpenGL and OpenGL ES. Everything
+//! else is called through [`gl`] on both, as ES functions are loaded into the
+//! desktop bindings under the same names. The ES extensions standing in for
+//! desktop core functions come from the `gles` bindings.
+
+use std::ffi::{c_void, CStr};
+
+use crate::gl::{self, types::*};
+use crate::gles;
+use crate::render_target::RenderTarget;
+use crate::shader::{self, is_gles};
+
+/// Loads the desktop and the ES bindings with `loader` for the context that
+/// is current on this thread, and detects which of the two it is.
+pub fn load_with(mut loader: impl FnMut(&'static str) -> *const c_void) {
+    gl::load_with(&mut loader);
+    gles::load_with(&mut loader);
+    shader::detect_api();
+}
+
+fn has_extension(name: &str) -> bool {
+    shader::extensions().any(|extension| extension == name)
+}
+
+/// Routes the messages of a debug context to `callback`. Returns `false` if
+/// the context cannot report them: on desktop gl if it was not created with
+/// the debug flag, on ES without `GL_KHR_debug`.
+pub fn debug_message_callback(callback: GLDEBUGPROC) -> bool {
+    unsafe {
+        if is_gles() {
+            if !has_extension("GL_KHR_debug") {
+                return false;
+            }
+            gles::DebugMessageCallbackKHR(callback, std::ptr::null());
+        } else {
+            let mut flags = 0;
+            gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
+            if flags as u32 & gl::CONTEXT_FLAG_DEBUG_BIT == 0 {
+                return false;
+            }
+            gl::DebugMessageCallback(callback, std::ptr::null());
+        }
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // messages are reported from within the call causing them
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    }
+    true
+}
+
+/// Sets the size of the points drawn next, or lets the vertex shader set it
+/// with `gl_PointSize` if `None`. On ES the vertex shader always sets it, so
+/// this does nothing. Undone by [`reset_point_size`].
+pub fn set_point_size(size: Option<f32>) {
+    if is_gles() {
+        return;
+    }
+    unsafe {
+        match size {
+            Some(size) => gl::PointSize(size),
+            None => gl::Enable(gl::PROGRAM_POINT_SIZE),
+        }
+    }
+}
+
+pub fn reset_point_size() {
+    if is_gles() {
+        return;
+    }
+    unsafe {
+        gl::Disable(gl::PROGRAM_POINT_SIZE);
+        gl::PointSize(1.0);
+    }
+}
+
+/// Sets the number of vertices of the patches drawn next. ES before 3.2 only
+/// has it with `GL_EXT_tessellation_shader`; without either, tessellation
+/// shaders do not compile in the first place.
+pub fn patch_vertices(count: i32) {
+    unsafe {
+        if is_gles() && gles::PatchParameteriEXT::is_loaded() {
+            gles::PatchParameteriEXT(gles::PATCH_VERTICES_EXT, count);
+        } else if gl::PatchParameteri::is_loaded() {
+            gl::PatchParameteri(gl::PATCH_VERTICES, count);
+        }
+    }
+}
+
+/// Sets the RGBA32F `texture` to zero. ES has no `glClearTexImage`, so there
+/// it is cleared as a framebuffer attachment.
+pub fn clear_texture(texture: u32) {
+    unsafe {
+        if !is_gles() {
+            gl::ClearTexImage(texture, 0, gl::RGBA, gl::FLOAT, std::ptr::null());
+            return;
+        }
+
+        let mut framebuffer = 0;
+        gl::GenFramebuffers(1, &mut framebuffer);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            texture,
+            0,
+        );
+        gl::ClearBufferfv(gl::COLOR, 0, [0.0f32; 4].as_ptr());
+        RenderTarget::unbind();
+        gl::DeleteFramebuffers(1, &framebuffer);
+    }
+}
+
+/// Sets the `len` words of the shader storage buffer `buffer` to zero. ES has
+/// no `glClearBufferData`, so there zeros are uploaded.
+pub fn clear_storage_buffer(buffer: u32, len: usize) {
+    unsafe {
+        gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, buffer);
+        if is_gles() {
+            let zeros = vec![0u32; len];
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                std::mem::size_of_val(zeros.as_slice()) as GLsizeiptr,
+                zeros.as_ptr().cast(),
+            );
+        } else {
+            gl::ClearBufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                gl::R32UI,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+        }
+        gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+    }
+}
+
+/// Binds the storage block `name` of `program` to `binding`. Returns `false`
+/// if the program has no such block, or on ES where the binding can only be
+/// set in the shader.
+pub fn storage_block_binding(program: u32, name: &CStr, binding: u32) -> bool {
+    if is_gles() {
+        return false;
+    }
+    unsafe {
+        let index = gl::GetProgramResourceIndex(program, gl::SHADER_STORAGE_BLOCK, name.as_ptr());
+        if index == gl::INVALID_INDEX {
+            return false;
+        }
+        gl::ShaderStorageBlockBinding(program, index, binding);
+    }
+    true
+}
+
+/// Points the sampler or image uniform `name` of the current `program` at
+/// texture or image `unit`. ES only allows that for samplers, images are
+/// bound in the shader with `layout(binding = ...)`.
+pub fn uniform_unit(program: u32, name: &CStr, unit: i32) {
+    unsafe {
+        let location = gl::GetUniformLocation(program, name.as_ptr());
+        if location == -1 {
+            return;
+        }
+        if is_gles() {
+            let mut index = gl::INVALID_INDEX;
+            gl::GetUniformIndices(program, 1, &name.as_ptr(), &mut index);
+            let mut kind = 0;
+            gl::GetActiveUniformsiv(program, 1, &index, gl::UNIFORM_TYPE, &mut kind);
+            // the image types are numbered contiguously
+            if (gl::IMAGE_1D..=gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY).contains(&(kind as _)) {
+                return;
+            }
+        }
+        gl::Uniform1i(location, unit);
+    }
+}
+
+/// Whether `GL_TIME_ELAPSED` queries are available, on ES only with
+/// `GL_EXT_disjoint_timer_query`.
+pub fn has_timer_queries() -> bool {
+    !is_gles() || has_extension("GL_EXT_disjoint_timer_query")
+}
+
+/// Waits for the result of `query`, see [`has_timer_queries`].
+pub fn query_result_u64(query: u32) -> u64 {
+    let mut result = 0;
+    unsafe {
+        if is_gles() {
+            gles::GetQueryObjectui64vEXT(query, gles::QUERY_RESULT_EXT, &mut result);
+        } else {
+            gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut result);
+        }
+    }
+    result
+}
@@ -1,6 +1,82 @@
 use std::path::PathBuf;
 
-use winit::event::{ElementState, KeyEvent, MouseButton};
+use serde::{Deserialize, Serialize};
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::PhysicalKey,
+};
+
+/// The parts of a key press an [`App`] sees. Unlike winit's `KeyEvent` it can
+/// be created outside of an event loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyInput {
+    pub physical_key: PhysicalKey,
+    pub state: ElementState,
+    pub repeat: bool,
+}
+
+impl From<&KeyEvent> for KeyInput {
+    fn from(event: &KeyEvent) -> Self {
+        Self {
+            physical_key: event.physical_key,
+            state: event.state,
+            repeat: event.repeat,
+        }
+    }
+}
+
+/// An input delivered to an [`App`], one for each of its `handle_*` methods.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum AppEvent {
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    MouseInput {
+        button: MouseButton,
+        state: ElementState,
+    },
+    MouseMotion {
+        x: f32,
+        y: f32,
+    },
+    /// In lines, positive when scrolling up.
+    MouseWheel {
+        delta: f32,
+    },
+    FileDrop(PathBuf),
+    Key(KeyInput),
+}
+
+impl AppEvent {
+    /// The event `event` is delivered as, if any. Empty resizes, which
+    /// minimized windows get, are dropped.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::Resized(size) if size.width != 0 && size.height != 0 => Self::Resize {
+                width: size.width,
+                height: size.height,
+            },
+            WindowEvent::KeyboardInput { event, .. } => Self::Key(event.into()),
+            WindowEvent::MouseInput { state, button, .. } => Self::MouseInput {
+                button: *button,
+                state: *state,
+            },
+            WindowEvent::CursorMoved { position, .. } => Self::MouseMotion {
+                x: position.x as _,
+                y: position.y as _,
+            },
+            WindowEvent::MouseWheel { delta, .. } => Self::MouseWheel {
+                delta: match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 50.0,
+                },
+            },
+            WindowEvent::DroppedFile(path) => Self::FileDrop(path.clone()),
+            _ => return None,
+        })
+    }
+}
 
 pub trait App {
     fn quit(&self) -> bool;
@@ -11,9 +87,29 @@ pub trait App {
 
     fn handle_mouse_motion_input(&mut self, mouse_position: (f32, f32));
 
+    /// `delta` is in lines, positive when scrolling up.
+    fn handle_mouse_wheel_input(&mut self, delta: f32);
+
     fn handle_file_drop_input(&mut self, path: PathBuf);
 
-    fn handle_key_input(&mut self, event: KeyEvent);
+    fn handle_key_input(&mut self, event: KeyInput);
 
     fn draw(&mut self);
+
+    /// Sets the clock, in seconds since the app started, until it is set
+    /// again. Replays use it to draw every frame at its recorded time. Apps
+    /// that do not animate can ignore it.
+    fn set_time(&mut self, _time: f32) {}
+
+    /// Calls the `handle_*` method (or `on_resize`) for `event`.
+    fn handle_event(&mut self, event: AppEvent) {
+        match event {
+            AppEvent::Resize { width, height } => self.on_resize(width, height),
+            AppEvent::MouseInput { button, state } => self.handle_mouse_input(button, state),
+            AppEvent::MouseMotion { x, y } => self.handle_mouse_motion_input((x, y)),
+            AppEvent::MouseWheel { delta } => self.handle_mouse_wheel_input(delta),
+            AppEvent::FileDrop(path) => self.handle_file_drop_input(path),
+            AppEvent::Key(input) => self.handle_key_input(input),
+        }
+    }
 }
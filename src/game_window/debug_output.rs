@@ -0,0 +1,72 @@
+use std::ffi::{c_void, CStr};
+
+use log::Level;
+
+use crate::compat;
+use crate::gl::{self, types::GLenum};
+
+/// Routes the messages of a debug context into the `log` crate, with the
+/// driver's severity as the level. Returns `false` if the context was not
+/// created with the debug flag, or lacks `GL_KHR_debug` on ES.
+pub fn install() -> bool {
+    compat::debug_message_callback(Some(callback))
+}
+
+extern "system" fn callback(
+    source: GLenum,
+    gltype: GLenum,
+    id: u32,
+    severity: GLenum,
+    length: i32,
+    message: *const gl::types::GLchar,
+    _user_param: *mut c_void,
+) {
+    let message = if message.is_null() {
+        String::new()
+    } else if length >= 0 {
+        let bytes = unsafe { std::slice::from_raw_parts(message.cast::<u8>(), length as usize) };
+        String::from_utf8_lossy(bytes).into_owned()
+    } else {
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    };
+
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH => Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => Level::Warn,
+        gl::DEBUG_SEVERITY_LOW => Level::Info,
+        _ => Level::Debug,
+    };
+    log::log!(
+        level,
+        "gl {} {} {}: {}",
+        source_name(source),
+        type_name(gltype),
+        id,
+        message.trim_end()
+    );
+}
+
+fn source_name(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(gltype: GLenum) -> &'static str {
+    match gltype {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        _ => "other",
+    }
+}
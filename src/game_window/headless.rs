@@ -0,0 +1,81 @@
+use anyhow::anyhow;
+
+use super::options::ContextOptions;
+#[cfg(not(target_os = "macos"))]
+use {
+    anyhow::Context,
+    glutin::{
+        api::egl::{context::PossiblyCurrentContext, device::Device, display::Display},
+        config::{ConfigSurfaceTypes, ConfigTemplateBuilder},
+        display::GlDisplay,
+    },
+    log::{debug, warn},
+    std::ffi::CString,
+};
+
+/// A gl context without a window or surface. Everything is rendered into
+/// framebuffer objects, which makes it usable from the command line and on
+/// machines without a display.
+pub struct HeadlessContext {
+    #[cfg(not(target_os = "macos"))]
+    _context: PossiblyCurrentContext,
+}
+
+impl HeadlessContext {
+    /// Creates a surfaceless context on the first EGL device and makes it current.
+    #[cfg(not(target_os = "macos"))]
+    pub fn new(options: ContextOptions) -> anyhow::Result<Self> {
+        let device = Device::query_devices()
+            .with_context(|| "querying EGL devices")?
+            .next()
+            .ok_or_else(|| anyhow!("no EGL device found"))?;
+        debug!(
+            "headless EGL device: {}",
+            device.name().or(device.vendor()).unwrap_or("unknown")
+        );
+
+        let display = unsafe { Display::with_device(&device, None) }
+            .with_context(|| "creating EGL display")?;
+
+        // there is no default framebuffer to multisample
+        let options = ContextOptions {
+            samples: None,
+            ..options
+        };
+        let template = options
+            .config_template(
+                ConfigTemplateBuilder::new()
+                    .with_alpha_size(8)
+                    .with_surface_type(ConfigSurfaceTypes::empty()),
+            )
+            .build();
+        let config = unsafe { display.find_configs(template) }
+            .with_context(|| "finding EGL configs")?
+            .next()
+            .ok_or_else(|| anyhow!("no EGL config found"))?;
+
+        let context_attributes = options.context_attributes(None);
+        let context = unsafe { display.create_context(&config, &context_attributes) }
+            .with_context(|| "creating gl context")?
+            .make_current_surfaceless()
+            .with_context(|| "making gl context current")?;
+
+        crate::compat::load_with(|symbol| {
+            let symbol = CString::new(symbol).unwrap(); // no way to void this unwrap
+            display.get_proc_address(symbol.as_c_str()).cast()
+        });
+
+        if options.debug && !super::debug_output::install() {
+            warn!("the driver did not create a debug context");
+        }
+
+        Ok(Self { _context: context })
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn new(_options: ContextOptions) -> anyhow::Result<Self> {
+        Err(anyhow!(
+            "headless rendering requires EGL, which is not available on macOS"
+        ))
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Context;
+use winit::event::WindowEvent;
+
+use crate::{
+    image::Image,
+    render_target::{self, Filter, Format, RenderTarget},
+};
+
+use super::{
+    app::{App, AppEvent},
+    headless::HeadlessContext,
+    options::ContextOptions,
+};
+
+/// Runs `f` with `fbo` as the default framebuffer, restoring the previous one
+/// afterwards.
+fn with_default_framebuffer<T>(fbo: u32, f: impl FnOnce() -> T) -> T {
+    let previous = render_target::default_framebuffer();
+    render_target::set_default_framebuffer(fbo);
+    let result = f();
+    render_target::set_default_framebuffer(previous);
+    result
+}
+
+/// Hosts an [`App`] in a window and gl context owned by someone else, e.g. an
+/// existing winit application. The owner forwards the window's events,
+/// calls [`Self::draw`] once per frame and swaps the buffers.
+///
+/// The app's gl context must be current, and loaded with
+/// [`crate::load_with`], whenever the host is used.
+pub struct AppHost<A> {
+    app: A,
+    framebuffer: u32,
+}
+
+impl<A: App> AppHost<A> {
+    pub fn new(app: A) -> Self {
+        Self {
+            app,
+            framebuffer: 0,
+        }
+    }
+
+    /// Presents into the framebuffer object `fbo` instead of the default
+    /// framebuffer of the context.
+    pub fn with_framebuffer(self, fbo: u32) -> Self {
+        Self {
+            framebuffer: fbo,
+            ..self
+        }
+    }
+
+    pub fn app(&self) -> &A {
+        &self.app
+    }
+
+    pub fn app_mut(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    pub fn into_app(self) -> A {
+        self.app
+    }
+
+    /// Forwards the parts of `event` the app handles and ignores the rest.
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        if let Some(event) = AppEvent::from_window_event(event) {
+            self.handle_event(event);
+        }
+    }
+
+    pub fn handle_event(&mut self, event: AppEvent) {
+        with_default_framebuffer(self.framebuffer, || self.app.handle_event(event));
+    }
+
+    /// Draws a frame. Returns `false` once the app wants to quit.
+    pub fn draw(&mut self) -> bool {
+        with_default_framebuffer(self.framebuffer, || {
+            RenderTarget::unbind();
+            self.app.draw();
+        });
+        !self.app.quit()
+    }
+}
+
+/// Hosts an [`App`] without a window, for tests and scripted renders. Events
+/// are synthetic and every frame is drawn into a framebuffer object that can
+/// be read back.
+pub struct HeadlessHost<A> {
+    // dropped before the framebuffer and context it draws with
+    host: AppHost<A>,
+    target: RenderTarget,
+    _context: HeadlessContext,
+}
+
+impl<A: App> HeadlessHost<A> {
+    /// Creates a headless context with `options` and the app in it, like a
+    /// window of `size` would.
+    pub fn new<ARG>(
+        options: ContextOptions,
+        size: (u32, u32),
+        app_constructor: fn(ARG) -> anyhow::Result<A>,
+        app_arg: ARG,
+    ) -> anyhow::Result<Self> {
+        let context = HeadlessContext::new(options)?;
+        let target = RenderTarget::new(size.0, size.1, Format::Rgba8, Filter::Nearest)
+            .with_context(|| "creating the frame buffer")?;
+        let app = with_default_framebuffer(target.fbo, || app_constructor(app_arg))
+            .with_context(|| "creating app")?;
+
+        let mut this = Self {
+            host: AppHost::new(app).with_framebuffer(target.fbo),
+            target,
+            _context: context,
+        };
+        this.send(AppEvent::Resize {
+            width: size.0,
+            height: size.1,
+        })?;
+        Ok(this)
+    }
+
+    pub fn app(&self) -> &A {
+        self.host.app()
+    }
+
+    pub fn app_mut(&mut self) -> &mut A {
+        self.host.app_mut()
+    }
+
+    /// The size of the frames, changed by [`AppEvent::Resize`].
+    pub fn size(&self) -> (u32, u32) {
+        (self.target.width, self.target.height)
+    }
+
+    /// Delivers `event` like a window would.
+    pub fn send(&mut self, event: AppEvent) -> anyhow::Result<()> {
+        if let AppEvent::Resize { width, height } = event {
+            self.target
+                .resize(width, height)
+                .with_context(|| "resizing the frame buffer")?;
+        }
+        self.host.handle_event(event);
+        Ok(())
+    }
+
+    /// Draws a frame. Returns `false` once the app wants to quit.
+    pub fn draw(&mut self) -> bool {
+        self.host.draw()
+    }
+
+    /// Reads back the last frame drawn.
+    pub fn frame(&self) -> Image {
+        Image::read_framebuffer(self.target.fbo, self.target.width, self.target.height)
+    }
+}
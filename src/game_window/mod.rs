@@ -1,4 +1,9 @@
 pub mod app;
+mod debug_output;
+pub mod headless;
+pub mod host;
+pub mod options;
+pub mod session;
 mod utils;
 
 use std::{ffi::CString, num::NonZeroU32};
@@ -6,28 +11,25 @@ use std::{ffi::CString, num::NonZeroU32};
 use anyhow::{anyhow, Context};
 use glutin::{
     config::{ConfigTemplateBuilder, GlConfig},
-    context::{ContextAttributesBuilder, NotCurrentGlContext, PossiblyCurrentContext},
+    context::{NotCurrentGlContext, PossiblyCurrentContext},
     display::{GetGlDisplay, GlDisplay},
     surface::{GlSurface, SwapInterval, WindowSurface},
 };
 use glutin_winit::{DisplayBuilder, GlWindow};
-use log::debug;
+use log::{debug, warn};
 use winit::{
     application::ApplicationHandler,
-    dpi::PhysicalSize,
-    event::WindowEvent,
+    event::{ElementState, KeyEvent, WindowEvent},
     event_loop::EventLoop,
+    keyboard::{KeyCode, PhysicalKey},
     raw_window_handle::HasWindowHandle,
     window::{Window, WindowAttributes},
 };
 
-use self::app::App;
-
-fn common_window_attributes(title: impl ToString) -> WindowAttributes {
-    Window::default_attributes()
-        .with_transparent(true)
-        .with_title(title.to_string())
-}
+use self::{
+    app::{App, AppEvent},
+    options::WindowOptions,
+};
 
 struct GlState {
     gl_context: PossiblyCurrentContext,
@@ -39,8 +41,7 @@ pub struct GameWindow<A, ARG> {
     template: ConfigTemplateBuilder,
     display_builder: DisplayBuilder,
     exit_state: anyhow::Result<()>,
-    title: String,
-    vsync: bool,
+    options: WindowOptions,
     gl_state: Option<GlState>,
 
     app: Option<A>,
@@ -49,31 +50,31 @@ pub struct GameWindow<A, ARG> {
 }
 
 impl<A: App, ARG> GameWindow<A, ARG> {
-    fn create_window_attributes(&self) -> WindowAttributes {
-        common_window_attributes(&self.title)
+    fn create_window_attributes(
+        &self,
+        event_loop: &winit::event_loop::ActiveEventLoop,
+    ) -> WindowAttributes {
+        self.options.window_attributes(event_loop)
     }
 
     pub fn new(
-        title: impl ToString,
-        vsync: bool,
+        options: WindowOptions,
         app_constructor: fn(ARG) -> anyhow::Result<A>,
         app_arg: ARG,
     ) -> Self {
-        let title = title.to_string();
+        let template = options.context.config_template(
+            ConfigTemplateBuilder::new()
+                .with_alpha_size(8)
+                .with_transparency(cfg!(target_os = "macos")),
+        );
 
-        let template = ConfigTemplateBuilder::new()
-            .with_alpha_size(8)
-            .with_transparency(cfg!(cgl_backend));
-
-        let display_builder =
-            DisplayBuilder::new().with_window_attributes(Some(common_window_attributes(&title)));
+        let display_builder = DisplayBuilder::new();
 
         GameWindow {
             template,
             display_builder,
             exit_state: Ok(()),
-            title,
-            vsync,
+            options,
             gl_state: None,
             app: None,
             app_constructor,
@@ -98,7 +99,10 @@ impl<A: App, ARG> GameWindow<A, ARG> {
         let (window, gl_config) = self
             .display_builder
             .clone()
-            .build(event_loop, self.template.clone(), utils::gl_config_picker)
+            .with_window_attributes(Some(self.create_window_attributes(event_loop)))
+            .build(event_loop, self.template.clone(), |configs| {
+                utils::gl_config_picker(configs, self.options.context.samples)
+            })
             .map_err(|err| anyhow!(err.to_string()))
             .with_context(|| "`display_builder.build`")?;
 
@@ -111,7 +115,7 @@ impl<A: App, ARG> GameWindow<A, ARG> {
 
         let gl_display = gl_config.display();
 
-        let context_attributes = ContextAttributesBuilder::new().build(raw_window_handle);
+        let context_attributes = self.options.context.context_attributes(raw_window_handle);
 
         let gl_context = unsafe { gl_display.create_context(&gl_config, &context_attributes) }
             .with_context(|| "creating gl context")?;
@@ -120,7 +124,7 @@ impl<A: App, ARG> GameWindow<A, ARG> {
             Some(window) => window,
             None => glutin_winit::finalize_window(
                 event_loop,
-                self.create_window_attributes(),
+                self.create_window_attributes(event_loop),
                 &gl_config,
             )?,
         };
@@ -136,11 +140,23 @@ impl<A: App, ARG> GameWindow<A, ARG> {
             .make_current(&gl_surface)
             .with_context(|| "making gl context current")?;
 
-        crate::gl::load_with(|symbol| {
+        crate::compat::load_with(|symbol| {
             let symbol = CString::new(symbol).unwrap(); // no way to void this unwrap
             gl_display.get_proc_address(symbol.as_c_str()).cast()
         });
 
+        if self.options.context.debug && !debug_output::install() {
+            warn!("the driver did not create a debug context");
+        }
+
+        if !crate::shader::supports_parallel_compile() {
+            if let Err(err) =
+                utils::spawn_compile_worker(&gl_config, &gl_context, &self.options.context)
+            {
+                debug!("could not start a shader compiler thread: {err:#}");
+            }
+        }
+
         if self.app.is_none() {
             let app = (self.app_constructor)(self.app_arg.take().unwrap())
                 .with_context(|| "creating app")?;
@@ -149,7 +165,7 @@ impl<A: App, ARG> GameWindow<A, ARG> {
             debug!("created app");
         }
 
-        let swap_interval = if self.vsync {
+        let swap_interval = if self.options.vsync {
             SwapInterval::Wait(NonZeroU32::new(1).unwrap())
         } else {
             SwapInterval::DontWait
@@ -166,6 +182,18 @@ impl<A: App, ARG> GameWindow<A, ARG> {
 
         Ok(())
     }
+
+    fn toggle_fullscreen(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(state) = &self.gl_state {
+            let fullscreen = match state.window.fullscreen() {
+                Some(_) => None,
+                None => Some(self.options.fullscreen_mode(event_loop)),
+            };
+            debug!("setting fullscreen: {:?}", fullscreen.is_some());
+            self.options.fullscreen = fullscreen.is_some();
+            state.window.set_fullscreen(fullscreen);
+        }
+    }
 }
 
 impl<A: App, ARG> ApplicationHandler for GameWindow<A, ARG> {
@@ -186,42 +214,33 @@ impl<A: App, ARG> ApplicationHandler for GameWindow<A, ARG> {
         event: WindowEvent,
     ) {
         match event {
-            WindowEvent::Resized(PhysicalSize::<u32> { width, height })
-                if width != 0 && height != 0 =>
-            {
-                if let Some(state) = &self.gl_state {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(KeyCode::F11),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => self.toggle_fullscreen(event_loop),
+            event => {
+                let Some(event) = AppEvent::from_window_event(&event) else {
+                    return;
+                };
+                if let (AppEvent::Resize { width, height }, Some(state)) = (&event, &self.gl_state)
+                {
                     state.gl_surface.resize(
                         &state.gl_context,
-                        NonZeroU32::new(width).unwrap(),
-                        NonZeroU32::new(height).unwrap(),
+                        NonZeroU32::new(*width).unwrap(),
+                        NonZeroU32::new(*height).unwrap(),
                     );
-                    if let Some(app) = &mut self.app {
-                        app.on_resize(width, height);
-                    }
-                }
-            }
-            WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::KeyboardInput { event, .. } => {
-                if let Some(app) = &mut self.app {
-                    app.handle_key_input(event);
                 }
-            }
-            WindowEvent::MouseInput { state, button, .. } => {
-                if let Some(app) = &mut self.app {
-                    app.handle_mouse_input(button, state);
-                }
-            }
-            WindowEvent::CursorMoved { position, .. } => {
-                if let Some(app) = &mut self.app {
-                    app.handle_mouse_motion_input((position.x as _, position.y as _));
-                }
-            }
-            WindowEvent::DroppedFile(file) => {
                 if let Some(app) = &mut self.app {
-                    app.handle_file_drop_input(file);
+                    app.handle_event(event);
                 }
             }
-            _ => (),
         }
     }
 
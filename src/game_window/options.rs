@@ -0,0 +1,147 @@
+use glutin::{
+    config::{Api, ConfigTemplateBuilder},
+    context::{
+        AsRawContext, ContextApi, ContextAttributes, ContextAttributesBuilder, GlProfile,
+        Robustness, Version,
+    },
+};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event_loop::ActiveEventLoop,
+    monitor::MonitorHandle,
+    raw_window_handle::RawWindowHandle,
+    window::{Fullscreen, Window, WindowAttributes, WindowLevel},
+};
+
+/// The flavour of OpenGL a context is created for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GlApi {
+    #[default]
+    Gl,
+    /// OpenGL ES 3.0 or newer.
+    Gles3,
+}
+
+/// What is requested from the driver when creating a gl context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ContextOptions {
+    pub api: GlApi,
+    /// `(major, minor)`. `None` lets the driver pick, which is usually the
+    /// newest version it supports.
+    pub version: Option<(u8, u8)>,
+    /// Ignored for OpenGL ES. `None` lets the driver pick.
+    pub profile: Option<GlProfile>,
+    pub robustness: Robustness,
+    /// Creates a debug context, whose messages are routed into the log.
+    pub debug: bool,
+    /// MSAA samples of the default framebuffer. `None` picks the config with
+    /// the most samples.
+    pub samples: Option<u8>,
+}
+
+impl ContextOptions {
+    pub fn config_template(&self, template: ConfigTemplateBuilder) -> ConfigTemplateBuilder {
+        let template = match self.api {
+            GlApi::Gl => template,
+            GlApi::Gles3 => template.with_api(Api::GLES3),
+        };
+        match self.samples {
+            Some(samples) => template.with_multisampling(samples),
+            None => template,
+        }
+    }
+
+    pub fn context_attributes(
+        &self,
+        raw_window_handle: Option<RawWindowHandle>,
+    ) -> ContextAttributes {
+        self.builder().build(raw_window_handle)
+    }
+
+    /// The attributes of a context sharing its objects with `context`, e.g.
+    /// to compile shaders on another thread.
+    pub fn shared_context_attributes(&self, context: &impl AsRawContext) -> ContextAttributes {
+        self.builder().with_sharing(context).build(None)
+    }
+
+    fn builder(&self) -> ContextAttributesBuilder {
+        let version = self
+            .version
+            .map(|(major, minor)| Version::new(major, minor));
+        let mut builder = ContextAttributesBuilder::new()
+            .with_robustness(self.robustness)
+            .with_debug(self.debug);
+        builder = match self.api {
+            GlApi::Gl => match version {
+                Some(version) => builder.with_context_api(ContextApi::OpenGl(Some(version))),
+                None => builder,
+            },
+            GlApi::Gles3 => builder.with_context_api(ContextApi::Gles(Some(
+                version.unwrap_or(Version::new(3, 0)),
+            ))),
+        };
+        if let (GlApi::Gl, Some(profile)) = (self.api, self.profile) {
+            builder = builder.with_profile(profile);
+        }
+        builder
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowOptions {
+    pub title: String,
+    pub vsync: bool,
+    pub size: Option<(u32, u32)>,
+    pub position: Option<(i32, i32)>,
+    pub fullscreen: bool,
+    /// Index into the available monitors. `None` picks the current monitor.
+    pub monitor: Option<usize>,
+    pub always_on_top: bool,
+    pub context: ContextOptions,
+}
+
+impl WindowOptions {
+    pub fn new(title: impl ToString) -> Self {
+        Self {
+            title: title.to_string(),
+            vsync: false,
+            size: None,
+            position: None,
+            fullscreen: false,
+            monitor: None,
+            always_on_top: false,
+            context: ContextOptions::default(),
+        }
+    }
+
+    pub fn fullscreen_mode(&self, event_loop: &ActiveEventLoop) -> Fullscreen {
+        let monitor: Option<MonitorHandle> = self
+            .monitor
+            .and_then(|index| event_loop.available_monitors().nth(index));
+        Fullscreen::Borderless(monitor)
+    }
+
+    pub fn window_attributes(&self, event_loop: &ActiveEventLoop) -> WindowAttributes {
+        let mut attributes = Window::default_attributes()
+            .with_transparent(true)
+            .with_title(&self.title);
+
+        if let Some((width, height)) = self.size {
+            attributes = attributes.with_inner_size(PhysicalSize::new(width, height));
+        }
+
+        if let Some((x, y)) = self.position {
+            attributes = attributes.with_position(PhysicalPosition::new(x, y));
+        }
+
+        if self.fullscreen {
+            attributes = attributes.with_fullscreen(Some(self.fullscreen_mode(event_loop)));
+        }
+
+        if self.always_on_top {
+            attributes = attributes.with_window_level(WindowLevel::AlwaysOnTop);
+        }
+
+        attributes
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{anyhow, bail, Context};
+use log::error;
+use serde::{Deserialize, Serialize};
+use winit::event::{ElementState, MouseButton};
+
+use crate::image::Image;
+
+use super::{
+    app::{App, AppEvent, KeyInput},
+    host::HeadlessHost,
+};
+
+/// The number of frames whose times are kept in memory before they are
+/// written to the session file, along with the events of those frames.
+const WRITE_INTERVAL: usize = 120;
+
+/// The first line of a session file, describing what was recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub shader: Option<PathBuf>,
+    /// The SHA-256 of the shader's source when the recording started.
+    pub source_sha256: Option<String>,
+    /// The size of the window when the first frame was drawn. Filled in by
+    /// the [`Recorder`].
+    pub size: (u32, u32),
+    /// The command line options the app was started with.
+    pub options: Vec<String>,
+}
+
+/// An event delivered before frame `frame` was drawn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub frame: u64,
+    /// Seconds since the recording started.
+    pub time: f32,
+    pub event: AppEvent,
+}
+
+/// One line of a session file after the [`Header`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Entry {
+    Event(RecordedEvent),
+    /// The times frames `first..first + times.len()` were drawn at, in
+    /// seconds since the recording started. Times are absolute rather than
+    /// deltas so a replay sets the exact same clock.
+    Frames {
+        first: u64,
+        times: Vec<f32>,
+    },
+}
+
+/// Wraps an [`App`] and records every event it gets and the time of every
+/// frame it draws into a session file: a JSON [`Header`] followed by one
+/// event or run of frame times per line. The recorder drives the app's clock, so the session
+/// can be replayed exactly by [`Session::replay`].
+///
+/// Entries are written every `WRITE_INTERVAL` frames and when the recorder is
+/// dropped.
+pub struct Recorder<A> {
+    app: A,
+    path: PathBuf,
+    /// `None` once writing failed.
+    writer: Option<BufWriter<File>>,
+    /// `Some` until it is written with the first frame.
+    header: Option<Header>,
+    started: Instant,
+    frame: u64,
+    /// The events and frame times that are not written yet.
+    events: Vec<RecordedEvent>,
+    times: Vec<f32>,
+}
+
+impl<A> Recorder<A> {
+    pub fn new(app: A, path: &Path, header: Header) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("creating `{}`", path.display()))?;
+        Ok(Self {
+            app,
+            path: path.to_path_buf(),
+            writer: Some(BufWriter::new(file)),
+            header: Some(header),
+            started: Instant::now(),
+            frame: 0,
+            events: Vec::new(),
+            times: Vec::new(),
+        })
+    }
+
+    pub fn app(&self) -> &A {
+        &self.app
+    }
+
+    /// Writes the header if it was not yet, then the pending events and
+    /// frame times, and flushes the file.
+    fn write_pending(&mut self) {
+        let Some(writer) = &mut self.writer else {
+            return;
+        };
+        let header = self.header.take();
+        let events = self.events.drain(..).map(Entry::Event);
+        let frames = (!self.times.is_empty()).then(|| Entry::Frames {
+            first: self.frame - self.times.len() as u64,
+            times: std::mem::take(&mut self.times),
+        });
+
+        let result = (|| {
+            if let Some(header) = &header {
+                write_line(writer, header)?;
+            }
+            for entry in events.chain(frames) {
+                write_line(writer, &entry)?;
+            }
+            writer.flush()?;
+            anyhow::Ok(())
+        })();
+        if let Err(err) = result {
+            error!(
+                "could not write to `{}`, stopped recording. Error: {}",
+                self.path.display(),
+                err
+            );
+            self.writer = None;
+        }
+    }
+}
+
+impl<A: App> Recorder<A> {
+    /// Sets the app's clock to now and returns it.
+    fn tick(&mut self) -> f32 {
+        let time = self.started.elapsed().as_secs_f32();
+        self.app.set_time(time);
+        time
+    }
+
+    fn record(&mut self, event: AppEvent) {
+        let time = self.tick();
+        self.events.push(RecordedEvent {
+            frame: self.frame,
+            time,
+            event: event.clone(),
+        });
+        self.app.handle_event(event);
+    }
+}
+
+impl<A: App> App for Recorder<A> {
+    fn quit(&self) -> bool {
+        self.app.quit()
+    }
+
+    fn on_resize(&mut self, width: u32, height: u32) {
+        if let Some(header) = &mut self.header {
+            header.size = (width, height);
+        }
+        self.record(AppEvent::Resize { width, height });
+    }
+
+    fn handle_mouse_input(&mut self, button: MouseButton, state: ElementState) {
+        self.record(AppEvent::MouseInput { button, state });
+    }
+
+    fn handle_mouse_motion_input(&mut self, mouse_position: (f32, f32)) {
+        self.record(AppEvent::MouseMotion {
+            x: mouse_position.0,
+            y: mouse_position.1,
+        });
+    }
+
+    fn handle_mouse_wheel_input(&mut self, delta: f32) {
+        self.record(AppEvent::MouseWheel { delta });
+    }
+
+    fn handle_file_drop_input(&mut self, path: PathBuf) {
+        self.record(AppEvent::FileDrop(path));
+    }
+
+    fn handle_key_input(&mut self, event: KeyInput) {
+        self.record(AppEvent::Key(event));
+    }
+
+    fn draw(&mut self) {
+        let time = self.tick();
+        self.app.draw();
+        self.times.push(time);
+        self.frame += 1;
+        if self.header.is_some() || self.times.len() >= WRITE_INTERVAL {
+            self.write_pending();
+        }
+    }
+}
+
+impl<A> Drop for Recorder<A> {
+    fn drop(&mut self) {
+        self.write_pending();
+    }
+}
+
+fn write_line(writer: &mut impl Write, value: &impl Serialize) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *writer, value)?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// A recorded session, read from the file written by a [`Recorder`].
+pub struct Session {
+    pub header: Header,
+    pub events: Vec<RecordedEvent>,
+    /// The time every frame was drawn at, in seconds since the recording started.
+    pub frame_times: Vec<f32>,
+}
+
+impl Session {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening `{}`", path.display()))?;
+        let mut lines = BufReader::new(file)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.as_ref().map_or(true, |line| !line.trim().is_empty()));
+        let mut next_line = || {
+            lines
+                .next()
+                .map(|(index, line)| {
+                    let line = line.with_context(|| format!("reading `{}`", path.display()))?;
+                    anyhow::Ok((index + 1, line))
+                })
+                .transpose()
+        };
+
+        let (_, line) = next_line()?.ok_or_else(|| anyhow!("`{}` is empty", path.display()))?;
+        let header = serde_json::from_str(&line)
+            .with_context(|| format!("parsing the header of `{}`", path.display()))?;
+        let mut events = Vec::new();
+        let mut frame_times = Vec::new();
+        while let Some((number, line)) = next_line()? {
+            let entry = serde_json::from_str(&line)
+                .with_context(|| format!("parsing line {} of `{}`", number, path.display()))?;
+            match entry {
+                Entry::Event(event) => events.push(event),
+                Entry::Frames { first, times } => {
+                    if first != frame_times.len() as u64 {
+                        bail!(
+                            "line {} of `{}` starts at frame {}, expected frame {}",
+                            number,
+                            path.display(),
+                            first,
+                            frame_times.len()
+                        );
+                    }
+                    frame_times.extend(times);
+                }
+            }
+        }
+        Ok(Self {
+            header,
+            events,
+            frame_times,
+        })
+    }
+
+    /// The number of frames drawn.
+    pub fn frames(&self) -> u64 {
+        self.frame_times.len() as u64
+    }
+
+    /// Delivers the recorded events to the app in `host` and draws the
+    /// recorded frames, with the clock set like it was while recording.
+    /// `on_frame` gets the number and contents of every frame drawn. Stops
+    /// early if the app quits.
+    pub fn replay<A: App>(
+        &self,
+        host: &mut HeadlessHost<A>,
+        mut on_frame: impl FnMut(u64, Image) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut events = self.events.iter().peekable();
+        for (frame, &time) in (0..).zip(&self.frame_times) {
+            while let Some(event) = events.next_if(|event| event.frame == frame) {
+                host.app_mut().set_time(event.time);
+                host.send(event.event.clone())?;
+            }
+            host.app_mut().set_time(time);
+            let running = host.draw();
+            on_frame(frame, host.frame())?;
+            if !running {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts what it gets and remembers the clock it was drawn at.
+    #[derive(Default)]
+    struct Counter {
+        events: usize,
+        time: f32,
+        times: Vec<f32>,
+    }
+
+    impl App for Counter {
+        fn quit(&self) -> bool {
+            false
+        }
+        fn on_resize(&mut self, _width: u32, _height: u32) {
+            self.events += 1;
+        }
+        fn handle_mouse_input(&mut self, _button: MouseButton, _state: ElementState) {
+            self.events += 1;
+        }
+        fn handle_mouse_motion_input(&mut self, _mouse_position: (f32, f32)) {
+            self.events += 1;
+        }
+        fn handle_mouse_wheel_input(&mut self, _delta: f32) {
+            self.events += 1;
+        }
+        fn handle_file_drop_input(&mut self, _path: PathBuf) {
+            self.events += 1;
+        }
+        fn handle_key_input(&mut self, _event: KeyInput) {
+            self.events += 1;
+        }
+        fn draw(&mut self) {
+            self.times.push(self.time);
+        }
+        fn set_time(&mut self, time: f32) {
+            self.time = time;
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}-{}.jsonl", name, std::process::id()))
+    }
+
+    fn header() -> Header {
+        Header {
+            shader: Some("shader.glsl".into()),
+            source_sha256: Some("00".to_string()),
+            size: (0, 0),
+            options: vec!["shader.glsl".to_string()],
+        }
+    }
+
+    #[test]
+    fn writes_events_and_frame_times_in_batches() {
+        let path = temp_path("session-batches");
+        let frames = WRITE_INTERVAL * 2 + 10;
+        let mut recorder = Recorder::new(Counter::default(), &path, header()).unwrap();
+        recorder.on_resize(320, 240);
+        for frame in 0..frames {
+            if frame % 100 == 50 {
+                recorder.handle_mouse_wheel_input(1.0);
+            }
+            recorder.draw();
+        }
+        let drawn = recorder.app().times.clone();
+        drop(recorder);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let session = Session::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // the header, 3 events and the times of the first frame, the two
+        // batches and the rest written on drop
+        assert_eq!(text.lines().count(), 1 + 3 + 4);
+        assert_eq!(session.header.size, (320, 240));
+        assert_eq!(session.header.options, header().options);
+        assert_eq!(session.frame_times, drawn);
+        let event_frames = session
+            .events
+            .iter()
+            .map(|event| event.frame)
+            .collect::<Vec<_>>();
+        assert_eq!(event_frames, [0, 50, 150]);
+    }
+
+    #[test]
+    fn rejects_missing_frames() {
+        let path = temp_path("session-gap");
+        let lines = [
+            serde_json::to_string(&header()).unwrap(),
+            serde_json::to_string(&Entry::Frames {
+                first: 0,
+                times: vec![0.0, 0.1],
+            })
+            .unwrap(),
+            serde_json::to_string(&Entry::Frames {
+                first: 3,
+                times: vec![0.3],
+            })
+            .unwrap(),
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let err = Session::load(&path).err().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(err
+            .to_string()
+            .ends_with("starts at frame 3, expected frame 2"));
+    }
+}
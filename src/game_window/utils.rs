@@ -1,12 +1,34 @@
-use glutin::config::{Config, GlConfig};
+use std::num::NonZeroU32;
 
-pub fn gl_config_picker(configs: Box<dyn Iterator<Item = Config> + '_>) -> Config {
+use anyhow::Context;
+use glutin::{
+    config::{Config, GlConfig},
+    context::{NotCurrentContext, NotCurrentGlContext, PossiblyCurrentContext},
+    display::{GetGlDisplay, GlDisplay},
+    surface::{PbufferSurface, SurfaceAttributesBuilder},
+};
+
+use super::options::ContextOptions;
+use crate::shader;
+
+/// Picks a config supporting transparency, then the one closest to the
+/// requested sample count, or the one with the most samples.
+pub fn gl_config_picker(
+    configs: Box<dyn Iterator<Item = Config> + '_>,
+    samples: Option<u8>,
+) -> Config {
     configs
         .reduce(|accum, config| {
             let transparency_check = config.supports_transparency().unwrap_or(false)
                 & !accum.supports_transparency().unwrap_or(false);
+            let samples_check = match samples {
+                Some(samples) => {
+                    config.num_samples().abs_diff(samples) < accum.num_samples().abs_diff(samples)
+                }
+                None => config.num_samples() > accum.num_samples(),
+            };
 
-            if transparency_check || config.num_samples() > accum.num_samples() {
+            if transparency_check || samples_check {
                 config
             } else {
                 accum
@@ -14,3 +36,33 @@ pub fn gl_config_picker(configs: Box<dyn Iterator<Item = Config> + '_>) -> Confi
         })
         .unwrap()
 }
+
+/// Starts [`shader::spawn_compile_worker`] with a context sharing its objects
+/// with `context`, made current without a surface on EGL and with a 1x1
+/// pbuffer otherwise.
+pub fn spawn_compile_worker(
+    config: &Config,
+    context: &PossiblyCurrentContext,
+    options: &ContextOptions,
+) -> anyhow::Result<()> {
+    let display = config.display();
+    let attributes = options.shared_context_attributes(context);
+    let shared = unsafe { display.create_context(config, &attributes) }
+        .with_context(|| "creating a shared gl context")?;
+    match shared {
+        #[cfg(not(target_os = "macos"))]
+        NotCurrentContext::Egl(shared) => {
+            shader::spawn_compile_worker(move || Ok(shared.make_current_surfaceless()?))
+        }
+        shared => {
+            let attributes = SurfaceAttributesBuilder::<PbufferSurface>::new()
+                .build(NonZeroU32::MIN, NonZeroU32::MIN);
+            let surface = unsafe { display.create_pbuffer_surface(config, &attributes) }
+                .with_context(|| "creating a pbuffer surface")?;
+            shader::spawn_compile_worker(move || {
+                let context = shared.make_current(&surface)?;
+                Ok((context, surface))
+            })
+        }
+    }
+}
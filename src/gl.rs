@@ -1 +1,3 @@
+#![allow(clippy::all)]
+
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
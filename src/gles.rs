@@ -0,0 +1,3 @@
+#![allow(clippy::all)]
+
+include!(concat!(env!("OUT_DIR"), "/gles_bindings.rs"));
@@ -0,0 +1,80 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use anyhow::Context;
+
+use crate::{gl, render_target::default_framebuffer};
+
+/// An 8-bit RGBA image stored row by row, starting from the top.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn black(width: u32, height: u32) -> Self {
+        let pixels = [0, 0, 0, 255].repeat((width * height) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Reads back the color attachment of `fbo` (`0` for the default framebuffer).
+    pub fn read_framebuffer(fbo: u32, width: u32, height: u32) -> Self {
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as _,
+                height as _,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, default_framebuffer());
+        }
+
+        // gl returns the rows bottom up
+        let row_len = (width * 4) as usize;
+        let pixels = pixels
+            .chunks_exact(row_len)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Writes the image as a PNG, embedding each `(keyword, text)` pair as a text chunk.
+    pub fn save_png(&self, path: &Path, text: &[(String, String)]) -> anyhow::Result<()> {
+        let file = File::create(path).with_context(|| format!("creating `{}`", path.display()))?;
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        for (keyword, text) in text {
+            encoder
+                .add_itxt_chunk(keyword.clone(), text.clone())
+                .with_context(|| format!("adding text chunk `{}`", keyword))?;
+        }
+
+        let mut writer = encoder
+            .write_header()
+            .with_context(|| "writing png header")?;
+        writer
+            .write_image_data(&self.pixels)
+            .with_context(|| "writing png data")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! Live-reloading fragment shader previews.
+//!
+//! The `shader-playground` binary is a thin command line interface over this
+//! crate. Other tools can use it to embed the playground in their own window,
+//! or to render shaders without one:
+//!
+//! - [`GameWindow`] runs an [`App`] such as [`ShaderPlayground`] in a window.
+//!   [`AppHost`] hosts one in a window or gl context owned by another
+//!   application, [`HeadlessHost`] without a window, driven by synthetic
+//!   [`AppEvent`]s. [`HeadlessContext`] gives a gl context without one, and
+//!   [`load_with`] loads a context of your own.
+//! - [`Recorder`] records the events an app gets into a [`Session`], which
+//!   can be replayed headlessly.
+//! - [`Renderer`] renders a shader to an [`Image`], with OpenGL or wgpu.
+//!   [`HeadlessRenderer`] does so without a window.
+//! - [`ShaderProgram`], [`PlaneBuffer`] and [`Uniforms`] are the pieces the
+//!   playground's render pipeline is built from.
+//! - [`FileWatcher`] reports changes to the files a shader is made of.
+//!
+//! [`ShaderPlaygroundArgs`] configures the playground with [`Filter`],
+//! [`CompareSource`], [`Dialect`], [`Procedural`], [`Primitive`] and
+//! [`Cull`], and shader errors are [`ShaderError`]s.
+
+#[doc(hidden)]
+pub mod compat;
+pub(crate) mod game_window;
+#[doc(hidden)]
+pub mod gl;
+pub(crate) mod gles;
+pub(crate) mod image;
+pub(crate) mod mesh;
+pub(crate) mod overlay;
+pub(crate) mod plane_buffer;
+pub(crate) mod program_cache;
+pub(crate) mod render_target;
+pub(crate) mod renderer;
+pub(crate) mod shader;
+pub(crate) mod shader_playground;
+pub(crate) mod storage_buffer;
+
+pub use compat::load_with;
+pub use game_window::{
+    app::{App, AppEvent, KeyInput},
+    headless::HeadlessContext,
+    host::{AppHost, HeadlessHost},
+    options::{ContextOptions, GlApi, WindowOptions},
+    session::{Header, Recorder, Session},
+    GameWindow,
+};
+pub use image::Image;
+pub use plane_buffer::PlaneBuffer;
+pub use render_target::Filter;
+pub use renderer::{headless::HeadlessRenderer, Renderer};
+pub use shader::{Error as ShaderError, ShaderProgram, Stage};
+pub use shader_playground::{
+    compare::CompareSource,
+    file_watcher::FileWatcher,
+    normalize::Dialect,
+    offscreen::FrameInputs,
+    scene::{Cull, Primitive, Procedural},
+    uniforms::Uniforms,
+    ShaderPlayground, ShaderPlaygroundArgs,
+};
+
+/// The internals the `shader-playground` binary is built from. They are not
+/// part of the API and may change at any time.
+#[doc(hidden)]
+pub mod cli_support {
+    pub use crate::{
+        render_target::{Format, RenderTarget},
+        renderer::is_wgsl,
+        shader_playground::{offscreen::OffscreenShader, screenshot::source_hash, validation},
+    };
+}
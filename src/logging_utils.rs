@@ -1,15 +1,25 @@
 use anyhow::Context;
 use log::LevelFilter;
-use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
+use simplelog::{ColorChoice, ConfigBuilder, TermLogger, TerminalMode};
+
+/// Crates whose logs are only shown with `--debug`, since they are very verbose.
+const VERBOSE_CRATES: [&str; 3] = ["wgpu_core", "wgpu_hal", "naga"];
 
 pub fn init_logger(force_debug: bool) -> anyhow::Result<()> {
+    let mut config = ConfigBuilder::new();
+    if !force_debug {
+        for name in VERBOSE_CRATES {
+            config.add_filter_ignore_str(name);
+        }
+    }
+
     TermLogger::init(
         if cfg!(debug_assertions) || force_debug {
             LevelFilter::Debug
         } else {
             LevelFilter::Info
         },
-        Config::default(),
+        config.build(),
         TerminalMode::Mixed,
         ColorChoice::Auto,
     )
@@ -1,37 +1,140 @@
 mod cli;
-mod game_window;
-mod gl;
+mod commands;
 mod logging_utils;
-mod plane_buffer;
-mod shader;
-mod shader_playground;
+
+use std::path::PathBuf;
 
 use clap::Parser;
+use glutin::context::{GlProfile, Robustness};
 use log::info;
-use shader_playground::ShaderPlaygroundArgs;
+use shader_playground::{
+    CompareSource, ContextOptions, Cull, Dialect, Filter, GameWindow, GlApi, Header, Primitive,
+    Procedural, Recorder, ShaderPlayground, ShaderPlaygroundArgs, Stage, WindowOptions,
+};
 
 fn main() -> anyhow::Result<()> {
     let cli = cli::Cli::parse();
 
     logging_utils::init_logger(cli.debug)?;
 
+    let context = ContextOptions {
+        api: match cli.gl_api {
+            cli::GlApiArg::Gl => GlApi::Gl,
+            cli::GlApiArg::Es3 => GlApi::Gles3,
+        },
+        version: cli.gl_version.map(|version| (version.major, version.minor)),
+        profile: cli.gl_profile.map(|profile| match profile {
+            cli::GlProfileArg::Core => GlProfile::Core,
+            cli::GlProfileArg::Compat => GlProfile::Compatibility,
+        }),
+        robustness: match cli.robustness {
+            cli::RobustnessArg::None => Robustness::NotRobust,
+            cli::RobustnessArg::NoError => Robustness::NoError,
+            cli::RobustnessArg::NoResetNotification => Robustness::RobustNoResetNotification,
+            cli::RobustnessArg::LoseContextOnReset => Robustness::RobustLoseContextOnReset,
+        },
+        debug: cli.gl_debug,
+        samples: cli.samples,
+    };
+
+    if let Some(command) = cli.command {
+        return match command {
+            cli::Command::Diff(args) => commands::diff::run(args, context),
+            cli::Command::Bench(args) => commands::bench::run(args, context),
+            cli::Command::Check(args) => commands::check::run(args),
+            cli::Command::Convert(args) => commands::convert::run(args),
+        };
+    }
+
     let args = ShaderPlaygroundArgs {
         file: cli.file,
         debouncer_ms: cli.debouncer_ms,
+        render_scale: cli.render_scale,
+        scale_filter: match cli.scale_filter {
+            cli::ScaleFilter::Nearest => Filter::Nearest,
+            cli::ScaleFilter::Linear => Filter::Linear,
+        },
+        fixed_resolution: cli.resolution.map(|size| (size.width, size.height)),
+        compare: match (cli.compare, cli.compare_rev) {
+            (Some(path), _) => Some(CompareSource::File(path)),
+            (None, Some(revision)) => Some(CompareSource::Revision(revision)),
+            (None, None) => None,
+        },
+        stages: [
+            (Stage::Vertex, cli.vertex),
+            (Stage::TessControl, cli.tess_control),
+            (Stage::TessEvaluation, cli.tess_evaluation),
+            (Stage::Geometry, cli.geometry),
+        ]
+        .into_iter()
+        .filter_map(|(stage, path)| Some((stage, path?)))
+        .collect(),
+        mesh: cli.mesh,
+        procedural: cli.vertex_count.map(|vertex_count| Procedural {
+            vertex_count,
+            primitive: match cli.primitive {
+                cli::PrimitiveType::Points => Primitive::Points,
+                cli::PrimitiveType::Lines => Primitive::Lines,
+                cli::PrimitiveType::LineStrip => Primitive::LineStrip,
+                cli::PrimitiveType::Triangles => Primitive::Triangles,
+            },
+            point_size: cli.point_size,
+        }),
+        depth_test: !cli.no_depth_test,
+        cull: match cli.cull {
+            cli::CullMode::None => Cull::None,
+            cli::CullMode::Back => Cull::Back,
+            cli::CullMode::Front => Cull::Front,
+        },
+        compute: cli.compute,
+        program_cache: !cli.no_program_cache,
+        defines: cli
+            .defines
+            .into_iter()
+            .map(|define| (define.name, define.value))
+            .collect(),
+        glsl: cli.glsl.map(|glsl| match glsl {
+            cli::GlslVersion::Glsl330 => Dialect::Glsl330,
+            cli::GlslVersion::Glsl450 => Dialect::Glsl450,
+            cli::GlslVersion::Es300 => Dialect::Es300,
+        }),
+        // shaders must swap in on the same frame when recording and replaying
+        background_compile: cli.record.is_none() && cli.replay.is_none(),
     };
 
-    let window = game_window::GameWindow::new(
-        "Shader Playground",
-        false,
-        shader_playground::ShaderPlayground::new,
-        args,
-    );
+    if let Some(session) = cli.replay {
+        let options = commands::replay::options(std::env::args());
+        return commands::replay::run(&session, &cli.replay_output, args, options, context);
+    }
 
-    info!("created window");
+    let window_options = WindowOptions {
+        vsync: cli.vsync,
+        size: cli.window_size.map(|size| (size.width, size.height)),
+        position: cli.window_position.map(|position| (position.x, position.y)),
+        fullscreen: cli.fullscreen,
+        monitor: cli.monitor,
+        always_on_top: cli.always_on_top,
+        context,
+        ..WindowOptions::new("Shader Playground")
+    };
 
     info!("running");
 
-    window.run()?;
+    match cli.record {
+        Some(session) => {
+            let header =
+                commands::replay::header(&args, commands::replay::options(std::env::args()));
+            GameWindow::new(
+                window_options,
+                |(args, session, header): (ShaderPlaygroundArgs, PathBuf, Header)| {
+                    Recorder::new(ShaderPlayground::new(args)?, &session, header)
+                },
+                (args, session, header),
+            )
+            .run()?
+        }
+        None => GameWindow::new(window_options, ShaderPlayground::new, args).run()?,
+    }
 
     info!("done");
 
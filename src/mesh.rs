@@ -0,0 +1,270 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::gl;
+use crate::gl::types::*;
+
+/// The attribute locations of a mesh vertex:
+/// `layout(location = 0) in vec3 aPosition`, `1` the normal and `2` the uv.
+pub const POSITION_LOCATION: u32 = 0;
+pub const NORMAL_LOCATION: u32 = 1;
+pub const UV_LOCATION: u32 = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+/// An indexed triangle list.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    /// Loads a Wavefront OBJ (`.obj`) or glTF (`.gltf`, `.glb`) file. All
+    /// meshes in the file are merged into one.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let mut mesh = match extension.as_deref() {
+            Some("obj") => Self::load_obj(path),
+            Some("gltf" | "glb") => Self::load_gltf(path),
+            _ => bail!("unsupported mesh format, expected `.obj`, `.gltf` or `.glb`"),
+        }
+        .with_context(|| format!("loading `{}`", path.display()))?;
+
+        if mesh.indices.is_empty() {
+            bail!("`{}` does not contain any triangles", path.display());
+        }
+        if mesh.vertices.iter().all(|v| v.normal == [0.0; 3]) {
+            mesh.compute_normals();
+        }
+        Ok(mesh)
+    }
+
+    fn load_obj(path: &Path) -> anyhow::Result<Self> {
+        let options = tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        };
+        let (models, _) = tobj::load_obj(path, &options)?;
+
+        let mut mesh = Self::default();
+        for model in models {
+            let m = model.mesh;
+            let offset = mesh.vertices.len() as u32;
+            for i in 0..m.positions.len() / 3 {
+                mesh.vertices.push(Vertex {
+                    position: [
+                        m.positions[3 * i],
+                        m.positions[3 * i + 1],
+                        m.positions[3 * i + 2],
+                    ],
+                    normal: element(&m.normals, i, 3)
+                        .map(|n| [n[0], n[1], n[2]])
+                        .unwrap_or_default(),
+                    uv: element(&m.texcoords, i, 2)
+                        .map(|uv| [uv[0], uv[1]])
+                        .unwrap_or_default(),
+                });
+            }
+            mesh.indices
+                .extend(m.indices.iter().map(|index| index + offset));
+        }
+        Ok(mesh)
+    }
+
+    fn load_gltf(path: &Path) -> anyhow::Result<Self> {
+        let (document, buffers, _) = gltf::import(path)?;
+
+        let mut mesh = Self::default();
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .context("the file does not contain a scene")?;
+        let mut nodes = scene
+            .nodes()
+            .map(|node| (node, Mat4::IDENTITY))
+            .collect::<Vec<_>>();
+        while let Some((node, parent)) = nodes.pop() {
+            let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+            nodes.extend(node.children().map(|child| (child, transform)));
+
+            let Some(node_mesh) = node.mesh() else {
+                continue;
+            };
+            let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+            for primitive in node_mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let Some(positions) = reader.read_positions() else {
+                    continue;
+                };
+
+                let offset = mesh.vertices.len() as u32;
+                let mut normals = reader.read_normals();
+                let mut uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32());
+                let mut count = 0;
+                for position in positions {
+                    let normal = normals.as_mut().and_then(Iterator::next);
+                    let uv = uvs.as_mut().and_then(Iterator::next);
+                    mesh.vertices.push(Vertex {
+                        position: transform.transform_point3(position.into()).into(),
+                        normal: normal
+                            .map(|normal| (normal_matrix * Vec3::from(normal)).normalize_or_zero())
+                            .unwrap_or_default()
+                            .into(),
+                        uv: uv.unwrap_or_default(),
+                    });
+                    count += 1;
+                }
+
+                match reader.read_indices() {
+                    Some(indices) => mesh
+                        .indices
+                        .extend(indices.into_u32().map(|index| index + offset)),
+                    None => mesh.indices.extend(offset..offset + count),
+                }
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// Sets every normal to the average of the normals of the faces sharing the vertex.
+    fn compute_normals(&mut self) {
+        let mut normals = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] =
+                [0, 1, 2].map(|i| Vec3::from(self.vertices[triangle[i] as usize].position));
+            // weighted by the area of the face
+            let normal = (b - a).cross(c - a);
+            for &index in triangle {
+                normals[index as usize] += normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.normalize_or_zero().into();
+        }
+    }
+
+    /// The axis aligned bounding box (`min`, `max`).
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        self.vertices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), vertex| {
+                let position = Vec3::from(vertex.position);
+                (min.min(position), max.max(position))
+            },
+        )
+    }
+}
+
+/// The `index`th group of `size` values, if `data` has one.
+fn element(data: &[f32], index: usize, size: usize) -> Option<&[f32]> {
+    data.get(index * size..(index + 1) * size)
+}
+
+/// A [`MeshData`] uploaded to the gpu.
+pub struct MeshBuffer {
+    pub vao: u32,
+    pub vbo: u32,
+    pub ebo: u32,
+    pub index_count: usize,
+    /// The bounding box (`min`, `max`) of the vertices.
+    pub bounds: (Vec3, Vec3),
+}
+
+impl MeshBuffer {
+    pub fn new(mesh: &MeshData) -> Self {
+        unsafe {
+            let mut vao = 0;
+            gl::GenVertexArrays(1, &mut vao);
+            let mut buffers = [0; 2];
+            gl::GenBuffers(2, buffers.as_mut_ptr());
+            let [vbo, ebo] = buffers;
+
+            gl::BindVertexArray(vao);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(mesh.vertices.as_slice()) as GLsizeiptr,
+                mesh.vertices.as_ptr().cast(),
+                gl::STATIC_DRAW,
+            );
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                std::mem::size_of_val(mesh.indices.as_slice()) as GLsizeiptr,
+                mesh.indices.as_ptr().cast(),
+                gl::STATIC_DRAW,
+            );
+
+            // set attributes
+            let stride = std::mem::size_of::<Vertex>() as GLsizei;
+            let attributes = [
+                (POSITION_LOCATION, 3, std::mem::offset_of!(Vertex, position)),
+                (NORMAL_LOCATION, 3, std::mem::offset_of!(Vertex, normal)),
+                (UV_LOCATION, 2, std::mem::offset_of!(Vertex, uv)),
+            ];
+            for (location, size, offset) in attributes {
+                gl::VertexAttribPointer(
+                    location,
+                    size,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    stride,
+                    offset as *const std::ffi::c_void,
+                );
+                gl::EnableVertexAttribArray(location);
+            }
+
+            // cleanup, the element buffer stays bound to the vao
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+
+            Self {
+                vao,
+                vbo,
+                ebo,
+                index_count: mesh.indices.len(),
+                bounds: mesh.bounds(),
+            }
+        }
+    }
+
+    /// Draws the triangles as `mode`, e.g. `gl::TRIANGLES` or `gl::PATCHES`.
+    pub fn draw(&self, mode: GLenum) {
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::DrawElements(
+                mode,
+                self.index_count as _,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+        }
+    }
+}
+
+impl Drop for MeshBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(2, [self.vbo, self.ebo].as_ptr());
+        }
+    }
+}
@@ -0,0 +1,218 @@
+mod font;
+
+use crate::gl;
+use crate::plane_buffer::PlaneBuffer;
+use crate::shader::ShaderProgram;
+
+use self::font::{GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+const VERTEX_SHADER: &str = include_str!("../res/basic_shaders/vert.glsl");
+const FRAG_SHADER_TEXT: &str = include_str!("../res/basic_shaders/frag_text.glsl");
+
+const CELL: (i32, i32) = (GLYPH_WIDTH as i32 + 1, GLYPH_HEIGHT as i32 + 2);
+const SCALE: i32 = 2;
+const PADDING: i32 = 2;
+
+/// Where a block of text is anchored in the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Locations {
+    text: i32,
+    font: i32,
+    size: i32,
+    cell: i32,
+    scale: i32,
+    padding: i32,
+    background: i32,
+    foreground: i32,
+}
+
+/// Draws blocks of monospaced ASCII text on top of the current framebuffer.
+pub struct TextOverlay {
+    program: ShaderProgram,
+    locations: Locations,
+    font_texture: u32,
+    text_texture: u32,
+}
+
+impl TextOverlay {
+    pub fn new() -> anyhow::Result<Self> {
+        let program = ShaderProgram::new(VERTEX_SHADER, FRAG_SHADER_TEXT)?;
+        let locations = Locations {
+            text: program.uniform_location("uText"),
+            font: program.uniform_location("uFont"),
+            size: program.uniform_location("uSize"),
+            cell: program.uniform_location("uCell"),
+            scale: program.uniform_location("uScale"),
+            padding: program.uniform_location("uPadding"),
+            background: program.uniform_location("uBackground"),
+            foreground: program.uniform_location("uForeground"),
+        };
+
+        // lay the glyphs out side by side
+        let font_width = GLYPHS.len() * GLYPH_WIDTH;
+        let mut font = vec![0u8; font_width * GLYPH_HEIGHT];
+        for (i, glyph) in GLYPHS.iter().enumerate() {
+            for (y, row) in glyph.iter().enumerate() {
+                for x in 0..GLYPH_WIDTH {
+                    if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                        font[y * font_width + i * GLYPH_WIDTH + x] = 255;
+                    }
+                }
+            }
+        }
+
+        unsafe {
+            let mut textures = [0; 2];
+            gl::GenTextures(2, textures.as_mut_ptr());
+            let [font_texture, text_texture] = textures;
+
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+            gl::BindTexture(gl::TEXTURE_2D, font_texture);
+            set_nearest_filter();
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::R8 as _,
+                font_width as _,
+                GLYPH_HEIGHT as _,
+                0,
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                font.as_ptr().cast(),
+            );
+
+            gl::BindTexture(gl::TEXTURE_2D, text_texture);
+            set_nearest_filter();
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            Ok(Self {
+                program,
+                locations,
+                font_texture,
+                text_texture,
+            })
+        }
+    }
+
+    /// The size in pixels of the box `lines` are drawn in.
+    pub fn measure(lines: &[impl AsRef<str>]) -> (i32, i32) {
+        let columns = columns(lines) as i32;
+        let rows = lines.len() as i32;
+        (
+            (columns * CELL.0 + 2 * PADDING) * SCALE,
+            (rows * CELL.1 + 2 * PADDING) * SCALE,
+        )
+    }
+
+    /// Draws `lines` in a box anchored to a corner of a window of `window_size`
+    /// pixels, `margin` pixels away from the edges.
+    pub fn draw(
+        &self,
+        plane: &PlaneBuffer,
+        window_size: (u32, u32),
+        anchor: Anchor,
+        margin: (i32, i32),
+        lines: &[impl AsRef<str>],
+    ) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let (width, height) = Self::measure(lines);
+        let x = match anchor {
+            Anchor::TopLeft | Anchor::BottomLeft => margin.0,
+            Anchor::TopRight | Anchor::BottomRight => window_size.0 as i32 - width - margin.0,
+        };
+        let y = match anchor {
+            Anchor::BottomLeft | Anchor::BottomRight => margin.1,
+            Anchor::TopLeft | Anchor::TopRight => window_size.1 as i32 - height - margin.1,
+        };
+
+        // one texel per character, non printable characters are left blank
+        let columns = columns(lines).max(1);
+        let mut text = vec![b' '; columns * lines.len()];
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.as_ref().bytes().enumerate() {
+                text[row * columns + column] = c;
+            }
+        }
+
+        unsafe {
+            gl::Viewport(x, y, width, height);
+
+            gl::Enable(gl::BLEND);
+            // keep the destination alpha so transparent windows stay opaque
+            gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA, gl::ZERO, gl::ONE);
+
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, self.text_texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::R8UI as _,
+                columns as _,
+                lines.len() as _,
+                0,
+                gl::RED_INTEGER,
+                gl::UNSIGNED_BYTE,
+                text.as_ptr().cast(),
+            );
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, self.font_texture);
+
+            self.program.use_program();
+            let l = self.locations;
+            gl::Uniform1i(l.font, 0);
+            gl::Uniform1i(l.text, 1);
+            gl::Uniform2f(l.size, width as _, height as _);
+            gl::Uniform2i(l.cell, CELL.0, CELL.1);
+            gl::Uniform1i(l.scale, SCALE);
+            gl::Uniform1i(l.padding, PADDING);
+            gl::Uniform4f(l.background, 0.0, 0.0, 0.0, 0.7);
+            gl::Uniform4f(l.foreground, 1.0, 1.0, 1.0, 1.0);
+
+            gl::BindVertexArray(plane.vao);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::Disable(gl::BLEND);
+        }
+    }
+}
+
+impl Drop for TextOverlay {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(2, [self.font_texture, self.text_texture].as_ptr());
+        }
+    }
+}
+
+fn columns(lines: &[impl AsRef<str>]) -> usize {
+    lines
+        .iter()
+        .map(|line| line.as_ref().len())
+        .max()
+        .unwrap_or(0)
+}
+
+unsafe fn set_nearest_filter() {
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as _);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as _);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+}
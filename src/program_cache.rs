@@ -0,0 +1,275 @@
+use std::{
+    ffi::CStr,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use log::{debug, warn};
+use sha2::{Digest, Sha256};
+
+use crate::gl;
+use crate::gl::types::*;
+use crate::shader::{ShaderProgram, Stage};
+
+/// The number of program binaries kept in memory.
+const MEMORY_CAPACITY: usize = 32;
+/// The on-disk cache is trimmed to this size, least recently used first.
+const DISK_CAPACITY: u64 = 64 * 1024 * 1024;
+const EXTENSION: &str = "bin";
+
+struct ProgramBinary {
+    format: GLenum,
+    data: Vec<u8>,
+}
+
+/// Linked programs stored with `glGetProgramBinary`, in memory and on disk.
+/// Entries are keyed by the source of every stage and the driver, so a
+/// driver update or a different GPU never sees a stale binary. Binaries the
+/// driver rejects anyway are dropped and the program is compiled as usual.
+pub struct ProgramCache {
+    /// `None` keeps the binaries in memory only.
+    dir: Option<PathBuf>,
+    enabled: bool,
+    /// The vendor, renderer and version strings of the driver.
+    driver: String,
+    /// Least recently used first.
+    memory: Vec<(String, ProgramBinary)>,
+}
+
+impl ProgramCache {
+    /// A cache in `dir`, or only in memory if there is none. The cache is
+    /// disabled if the driver has no program binary formats.
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let mut formats = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        if formats == 0 {
+            debug!("the driver does not support program binaries, caching is disabled");
+        }
+
+        let driver = [gl::VENDOR, gl::RENDERER, gl::VERSION]
+            .map(gl_string)
+            .join("\n");
+        Self {
+            dir,
+            enabled: formats > 0,
+            driver,
+            memory: Vec::new(),
+        }
+    }
+
+    /// A cache that never stores anything.
+    pub fn disabled() -> Self {
+        Self {
+            dir: None,
+            enabled: false,
+            driver: String::new(),
+            memory: Vec::new(),
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/shader-playground/programs`, falling back to
+    /// `~/.cache` and `%LOCALAPPDATA%`.
+    pub fn default_dir() -> Option<PathBuf> {
+        let cache = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+        Some(cache.join("shader-playground").join("programs"))
+    }
+
+    pub fn key(&self, stages: &[(Stage, &str)]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.driver.as_bytes());
+        for (stage, source) in stages {
+            hasher.update(format!("\0{}\0{}\0", stage, source.len()).as_bytes());
+            hasher.update(source.as_bytes());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Creates the program from a cached binary. Returns `None` on a miss or
+    /// if the driver rejects the binary.
+    pub fn load(&mut self, key: &str) -> Option<ShaderProgram> {
+        if !self.enabled {
+            return None;
+        }
+
+        match self.memory.iter().position(|(k, _)| k == key) {
+            Some(index) => {
+                // move to the most recently used end
+                let entry = self.memory.remove(index);
+                self.memory.push(entry);
+                debug!("program cache: memory hit");
+            }
+            None => {
+                let binary = self.read_file(key)?;
+                self.remember(key, binary);
+                debug!("program cache: disk hit");
+            }
+        }
+
+        let (_, binary) = self.memory.last()?;
+        match program_from_binary(binary) {
+            Some(program) => Some(program),
+            None => {
+                warn!("program cache: the driver rejected a cached binary, recompiling");
+                self.memory.retain(|(k, _)| k != key);
+                if let Some(path) = self.path(key) {
+                    let _ = std::fs::remove_file(path);
+                }
+                None
+            }
+        }
+    }
+
+    /// Stores the binary of a program linked with `PROGRAM_BINARY_RETRIEVABLE_HINT`.
+    pub fn store(&mut self, key: &str, program: &ShaderProgram) {
+        if !self.enabled {
+            return;
+        }
+        let Some(binary) = program_binary(program) else {
+            return;
+        };
+
+        if let Some(path) = self.path(key) {
+            if let Err(err) = write_file(&path, &binary) {
+                warn!(
+                    "program cache: could not write `{}`. Error: {}",
+                    path.display(),
+                    err
+                );
+            }
+            self.trim_dir();
+        }
+        self.remember(key, binary);
+    }
+
+    fn remember(&mut self, key: &str, binary: ProgramBinary) {
+        if self.memory.len() >= MEMORY_CAPACITY {
+            self.memory.remove(0);
+        }
+        self.memory.push((key.to_string(), binary));
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(key).with_extension(EXTENSION))
+    }
+
+    fn read_file(&self, key: &str) -> Option<ProgramBinary> {
+        let path = self.path(key)?;
+        let bytes = std::fs::read(&path).ok()?;
+        let (format, data) = bytes.split_first_chunk::<4>()?;
+
+        // keep recently used entries from being trimmed
+        if let Ok(file) = std::fs::File::options().append(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+
+        Some(ProgramBinary {
+            format: u32::from_le_bytes(*format),
+            data: data.to_vec(),
+        })
+    }
+
+    /// Deletes the least recently used files until the cache fits `DISK_CAPACITY`.
+    fn trim_dir(&self) {
+        let Some(entries) = self
+            .dir
+            .as_ref()
+            .and_then(|dir| std::fs::read_dir(dir).ok())
+        else {
+            return;
+        };
+        let mut files = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|e| e == EXTENSION))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+        for (_, len, path) in files {
+            if size <= DISK_CAPACITY {
+                break;
+            }
+            debug!("program cache: evicting `{}`", path.display());
+            if std::fs::remove_file(&path).is_ok() {
+                size -= len;
+            }
+        }
+    }
+}
+
+fn write_file(path: &Path, binary: &ProgramBinary) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut bytes = Vec::with_capacity(4 + binary.data.len());
+    bytes.extend_from_slice(&binary.format.to_le_bytes());
+    bytes.extend_from_slice(&binary.data);
+    std::fs::write(path, bytes)
+}
+
+fn program_binary(program: &ShaderProgram) -> Option<ProgramBinary> {
+    let mut len = 0;
+    unsafe {
+        gl::GetProgramiv(program.0, gl::PROGRAM_BINARY_LENGTH, &mut len);
+    }
+    if len <= 0 {
+        return None;
+    }
+
+    let mut data = vec![0u8; len as usize];
+    let mut format = 0;
+    unsafe {
+        gl::GetProgramBinary(
+            program.0,
+            len,
+            &mut len,
+            &mut format,
+            data.as_mut_ptr().cast(),
+        );
+    }
+    data.truncate(len.max(0) as usize);
+    Some(ProgramBinary { format, data })
+}
+
+fn program_from_binary(binary: &ProgramBinary) -> Option<ShaderProgram> {
+    unsafe {
+        let program = gl::CreateProgram();
+        gl::ProgramBinary(
+            program,
+            binary.format,
+            binary.data.as_ptr().cast(),
+            binary.data.len() as _,
+        );
+
+        let mut success = gl::FALSE as GLint;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+        if success != gl::TRUE as GLint {
+            gl::DeleteProgram(program);
+            return None;
+        }
+        Some(ShaderProgram(program))
+    }
+}
+
+fn gl_string(name: GLenum) -> String {
+    unsafe {
+        let string = gl::GetString(name);
+        if string.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr(string.cast()).to_string_lossy().into_owned()
+    }
+}
@@ -0,0 +1,343 @@
+use std::cell::Cell;
+
+use crate::gl;
+use crate::gl::types::*;
+use crate::image::Image;
+use crate::plane_buffer::PlaneBuffer;
+use crate::shader::ShaderProgram;
+
+const VERTEX_SHADER: &str = include_str!("res/basic_shaders/vert.glsl");
+const FRAG_SHADER_PRESENT: &str = include_str!("res/basic_shaders/frag_present.glsl");
+
+thread_local! {
+    static DEFAULT_FRAMEBUFFER: Cell<u32> = const { Cell::new(0) };
+}
+
+/// The framebuffer that is drawn to when no [`RenderTarget`] is bound, for
+/// the context current on this thread. `0` unless the host presents into a
+/// framebuffer object of its own.
+pub fn default_framebuffer() -> u32 {
+    DEFAULT_FRAMEBUFFER.get()
+}
+
+/// Sets the [`default_framebuffer`] of the context current on this thread.
+pub fn set_default_framebuffer(fbo: u32) {
+    DEFAULT_FRAMEBUFFER.set(fbo);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+impl Filter {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Self::Nearest => gl::NEAREST,
+            Self::Linear => gl::LINEAR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Rgba8,
+    /// Keeps the exact values written by the shader, including negatives, NaNs and infinities.
+    Rgba32F,
+}
+
+impl Format {
+    fn internal_format(self) -> GLenum {
+        match self {
+            Self::Rgba8 => gl::RGBA8,
+            Self::Rgba32F => gl::RGBA32F,
+        }
+    }
+
+    fn pixel_type(self) -> GLenum {
+        match self {
+            Self::Rgba8 => gl::UNSIGNED_BYTE,
+            Self::Rgba32F => gl::FLOAT,
+        }
+    }
+}
+
+/// A framebuffer with a color texture and a depth-stencil attachment.
+pub struct RenderTarget {
+    pub fbo: u32,
+    pub texture: u32,
+    /// A 24-bit depth, 8-bit stencil renderbuffer used when drawing meshes.
+    pub depth: u32,
+    pub width: u32,
+    pub height: u32,
+    pub format: Format,
+}
+
+impl RenderTarget {
+    pub fn new(width: u32, height: u32, format: Format, filter: Filter) -> anyhow::Result<Self> {
+        unsafe {
+            // create texture
+            let mut texture = 0;
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                filter.gl_enum() as _,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                filter.gl_enum() as _,
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            // create depth buffer
+            let mut depth = 0;
+            gl::GenRenderbuffers(1, &mut depth);
+
+            // create fbo
+            let mut fbo = 0;
+            gl::GenFramebuffers(1, &mut fbo);
+
+            let mut this = Self {
+                fbo,
+                texture,
+                depth,
+                width: 0,
+                height: 0,
+                format,
+            };
+            this.resize(width, height)?;
+
+            Ok(this)
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) -> anyhow::Result<()> {
+        let width = width.max(1);
+        let height = height.max(1);
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+
+        unsafe {
+            // storage
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                self.format.internal_format() as _,
+                width as _,
+                height as _,
+                0,
+                gl::RGBA,
+                self.format.pixel_type(),
+                std::ptr::null(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                width as _,
+                height as _,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            // attach
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.texture,
+                0,
+            );
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth,
+            );
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, default_framebuffer());
+
+            if status != gl::FRAMEBUFFER_COMPLETE {
+                anyhow::bail!("framebuffer is incomplete (status: {:#x})", status);
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+
+        Ok(())
+    }
+
+    /// Binds the target for drawing and sets the viewport to cover all of it.
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+            gl::Viewport(0, 0, self.width as _, self.height as _);
+        }
+    }
+
+    /// Binds the [`default_framebuffer`] again.
+    pub fn unbind() {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, default_framebuffer());
+        }
+    }
+
+    /// Replaces the contents with `image`, which must have the same size.
+    pub fn upload(&self, image: &Image) {
+        // gl expects the rows bottom up
+        let row_len = (image.width * 4) as usize;
+        let pixels = image
+            .pixels
+            .chunks_exact(row_len)
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                0,
+                0,
+                image.width as _,
+                image.height as _,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr().cast(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Reads back the value of a single pixel, measured from the bottom left.
+    pub fn read_pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        let mut value = [0.0f32; 4];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                x as _,
+                y as _,
+                1,
+                1,
+                gl::RGBA,
+                gl::FLOAT,
+                value.as_mut_ptr().cast(),
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, default_framebuffer());
+        }
+        value
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &[self.fbo][0]);
+            gl::DeleteTextures(1, &[self.texture][0]);
+            gl::DeleteRenderbuffers(1, &[self.depth][0]);
+        }
+    }
+}
+
+/// Draws the contents of a [`RenderTarget`] onto the currently bound framebuffer.
+pub struct Presenter {
+    program: ShaderProgram,
+    texture_location: i32,
+    uv_rect_location: i32,
+    nearest_sampler: u32,
+}
+
+impl Presenter {
+    pub fn new() -> anyhow::Result<Self> {
+        let program = ShaderProgram::new(VERTEX_SHADER, FRAG_SHADER_PRESENT)?;
+        let texture_location = program.uniform_location("uTexture");
+        let uv_rect_location = program.uniform_location("uUvRect");
+
+        let mut nearest_sampler = 0;
+        unsafe {
+            gl::GenSamplers(1, &mut nearest_sampler);
+            let filter = Filter::Nearest.gl_enum() as _;
+            gl::SamplerParameteri(nearest_sampler, gl::TEXTURE_MIN_FILTER, filter);
+            gl::SamplerParameteri(nearest_sampler, gl::TEXTURE_MAG_FILTER, filter);
+            let wrap = gl::CLAMP_TO_EDGE as _;
+            gl::SamplerParameteri(nearest_sampler, gl::TEXTURE_WRAP_S, wrap);
+            gl::SamplerParameteri(nearest_sampler, gl::TEXTURE_WRAP_T, wrap);
+        }
+
+        Ok(Self {
+            program,
+            texture_location,
+            uv_rect_location,
+            nearest_sampler,
+        })
+    }
+
+    /// Draws `target` stretched over the viewport `(x, y, width, height)`.
+    pub fn present(
+        &self,
+        target: &RenderTarget,
+        plane: &PlaneBuffer,
+        viewport: (i32, i32, i32, i32),
+    ) {
+        self.present_region(target, plane, viewport, (0.0, 0.0, 1.0, 1.0), false);
+    }
+
+    /// Draws the region `uv_rect` (`u`, `v`, `width`, `height`) of `target`
+    /// stretched over the viewport `(x, y, width, height)`. `nearest` overrides
+    /// the target's filter, showing individual pixels.
+    pub fn present_region(
+        &self,
+        target: &RenderTarget,
+        plane: &PlaneBuffer,
+        viewport: (i32, i32, i32, i32),
+        uv_rect: (f32, f32, f32, f32),
+        nearest: bool,
+    ) {
+        unsafe {
+            gl::Viewport(viewport.0, viewport.1, viewport.2, viewport.3);
+
+            self.program.use_program();
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, target.texture);
+            if nearest {
+                gl::BindSampler(0, self.nearest_sampler);
+            }
+            gl::Uniform1i(self.texture_location, 0);
+            gl::Uniform4f(
+                self.uv_rect_location,
+                uv_rect.0,
+                uv_rect.1,
+                uv_rect.2,
+                uv_rect.3,
+            );
+
+            gl::BindVertexArray(plane.vao);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+
+            gl::BindSampler(0, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+}
+
+impl Drop for Presenter {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &self.nearest_sampler);
+        }
+    }
+}
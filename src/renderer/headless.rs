@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{
+    game_window::{headless::HeadlessContext, options::ContextOptions},
+    image::Image,
+    shader,
+    shader_playground::offscreen::FrameInputs,
+};
+
+use super::Renderer;
+
+/// A renderer that brings its own context, for rendering shaders without a
+/// window.
+pub struct HeadlessRenderer {
+    // dropped before the context it was created in
+    renderer: Box<dyn Renderer>,
+    _context: Option<HeadlessContext>,
+}
+
+impl HeadlessRenderer {
+    /// The renderer for the shader at `path`: wgpu for WGSL, gl for anything
+    /// else. A gl context is created with `options` unless the shader is WGSL.
+    pub fn for_path(path: &Path, options: ContextOptions) -> anyhow::Result<Self> {
+        // WGSL shaders are rendered with wgpu, which creates its own device
+        let context = if super::is_wgsl(path) {
+            None
+        } else {
+            Some(HeadlessContext::new(options).with_context(|| "creating a headless gl context")?)
+        };
+        Ok(Self {
+            renderer: super::for_path(path)?,
+            _context: context,
+        })
+    }
+
+    /// Renders one frame of the shader at `path`.
+    pub fn render_file(
+        path: &Path,
+        options: ContextOptions,
+        inputs: FrameInputs,
+    ) -> anyhow::Result<Image> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("reading `{}`", path.display()))?;
+        let mut renderer = Self::for_path(path, options)?;
+        renderer
+            .load(&source)
+            .with_context(|| format!("compiling `{}`", path.display()))?;
+        renderer.render_image(inputs)
+    }
+}
+
+impl Renderer for HeadlessRenderer {
+    fn load(&mut self, source: &str) -> shader::Result<()> {
+        self.renderer.load(source)
+    }
+
+    fn render_image(&mut self, inputs: FrameInputs) -> anyhow::Result<Image> {
+        self.renderer.render_image(inputs)
+    }
+}
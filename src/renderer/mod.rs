@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use crate::{image::Image, shader, shader_playground::offscreen::FrameInputs};
+
+pub mod headless;
+pub mod opengl;
+pub mod webgpu;
+
+/// Renders a fragment shader over the whole frame, independent of the
+/// graphics API behind it.
+pub trait Renderer {
+    /// Compiles `source`, replacing the current shader. On error the
+    /// previous shader is kept.
+    fn load(&mut self, source: &str) -> shader::Result<()>;
+
+    /// Renders one frame with the current shader and reads it back. Without
+    /// a shader the frame is black.
+    fn render_image(&mut self, inputs: FrameInputs) -> anyhow::Result<Image>;
+}
+
+/// Whether `path` is a WGSL shader, which is rendered with wgpu instead of gl.
+pub fn is_wgsl(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wgsl"))
+}
+
+/// The renderer for the shader at `path`: wgpu for WGSL, gl for anything else.
+/// The gl renderer needs a current context.
+pub fn for_path(path: &Path) -> anyhow::Result<Box<dyn Renderer>> {
+    Ok(if is_wgsl(path) {
+        Box::new(webgpu::WgpuRenderer::new()?)
+    } else {
+        Box::new(opengl::GlRenderer::new()?)
+    })
+}
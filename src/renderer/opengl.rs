@@ -0,0 +1,39 @@
+use anyhow::Context;
+
+use crate::{
+    image::Image,
+    plane_buffer::PlaneBuffer,
+    shader,
+    shader_playground::offscreen::{FrameInputs, OffscreenShader},
+};
+
+use super::Renderer;
+
+/// Renders GLSL shaders with the current gl context.
+pub struct GlRenderer {
+    plane: PlaneBuffer,
+    shader: Option<OffscreenShader>,
+}
+
+impl GlRenderer {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            plane: PlaneBuffer::new().with_context(|| "creating plane buffer")?,
+            shader: None,
+        })
+    }
+}
+
+impl Renderer for GlRenderer {
+    fn load(&mut self, source: &str) -> shader::Result<()> {
+        self.shader = Some(OffscreenShader::from_source(source)?);
+        Ok(())
+    }
+
+    fn render_image(&mut self, inputs: FrameInputs) -> anyhow::Result<Image> {
+        match &self.shader {
+            Some(shader) => shader.render_image(&self.plane, inputs),
+            None => Ok(Image::black(inputs.resolution.0, inputs.resolution.1)),
+        }
+    }
+}
@@ -0,0 +1,511 @@
+use std::{
+    sync::mpsc::{self, Sender},
+    thread::JoinHandle,
+};
+
+use anyhow::{anyhow, Context};
+use log::{error, info};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    image::Image,
+    shader::{self, Stage},
+    shader_playground::{offscreen::FrameInputs, preprocessor::find_call},
+};
+
+use super::Renderer;
+
+const VERTEX_SHADER: &str = include_str!("../res/basic_shaders/vert.wgsl");
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+/// Every uniform has its own binding in group 0, at a multiple of this offset
+/// into the uniform buffer. 256 bytes is the largest alignment wgpu allows
+/// adapters to require.
+const UNIFORM_STRIDE: u64 = 256;
+
+/// The playground's uniforms with their binding and type, declared for
+/// shaders that use them without declaring them.
+pub(crate) const UNIFORM_DECLARATIONS: [(&str, u32, &str); 3] = [
+    ("uResolution", 0, "vec2<f32>"),
+    ("uMouse", 1, "vec2<f32>"),
+    ("uTime", 2, "f32"),
+];
+
+/// Renders WGSL shaders with wgpu, on any backend it finds. `WGPU_BACKEND`
+/// (e.g. `vulkan` or `gl`) restricts the choice.
+///
+/// wgpu's gl backend makes its own context current while it works, so the
+/// device lives on a thread of its own and leaves the caller's context alone.
+pub struct WgpuRenderer {
+    requests: Option<Sender<Request>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+enum Request {
+    Load(String, Sender<shader::Result<()>>),
+    Render(FrameInputs, Sender<anyhow::Result<Image>>),
+}
+
+impl WgpuRenderer {
+    pub fn new() -> anyhow::Result<Self> {
+        let (requests, receiver) = mpsc::channel();
+        let (created, created_receiver) = mpsc::channel();
+        let thread = std::thread::Builder::new()
+            .name("wgpu".to_string())
+            .spawn(move || {
+                let mut device = match Device::new() {
+                    Ok(device) => {
+                        let _ = created.send(Ok(()));
+                        device
+                    }
+                    Err(err) => {
+                        let _ = created.send(Err(err));
+                        return;
+                    }
+                };
+                for request in receiver {
+                    match request {
+                        Request::Load(source, reply) => {
+                            let _ = reply.send(device.load(&source));
+                        }
+                        Request::Render(inputs, reply) => {
+                            let _ = reply.send(device.render_image(inputs));
+                        }
+                    }
+                }
+            })
+            .with_context(|| "spawning the wgpu thread")?;
+        created_receiver
+            .recv()
+            .map_err(|_| anyhow!("the wgpu thread exited"))??;
+
+        Ok(Self {
+            requests: Some(requests),
+            thread: Some(thread),
+        })
+    }
+
+    /// Sends a request to the device thread and waits for its reply.
+    fn request<T>(&self, request: impl FnOnce(Sender<T>) -> Request) -> Option<T> {
+        let (reply, receiver) = mpsc::channel();
+        self.requests.as_ref()?.send(request(reply)).ok()?;
+        receiver.recv().ok()
+    }
+}
+
+impl Renderer for WgpuRenderer {
+    fn load(&mut self, source: &str) -> shader::Result<()> {
+        self.request(|reply| Request::Load(source.to_string(), reply))
+            .unwrap_or_else(|| {
+                Err(shader::Error::LinkError(
+                    "the wgpu thread exited".to_string(),
+                ))
+            })
+    }
+
+    fn render_image(&mut self, inputs: FrameInputs) -> anyhow::Result<Image> {
+        self.request(|reply| Request::Render(inputs, reply))
+            .unwrap_or_else(|| Err(anyhow!("the wgpu thread exited")))
+    }
+}
+
+impl Drop for WgpuRenderer {
+    fn drop(&mut self) {
+        // closing the channel ends the thread
+        self.requests = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The wgpu state, owned by the renderer's thread.
+struct Device {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    vertex: wgpu::ShaderModule,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline_layout: wgpu::PipelineLayout,
+    pipeline: Option<wgpu::RenderPipeline>,
+    /// The color texture and the buffer it is read back through, for the
+    /// resolution of the last frame.
+    target: Option<(wgpu::Texture, wgpu::Buffer)>,
+}
+
+impl Device {
+    fn new() -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::from_env_or_default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::from_env().unwrap_or_default(),
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))
+        .with_context(|| "requesting a wgpu adapter")?;
+        let adapter_info = adapter.get_info();
+        info!(
+            "wgpu adapter: {} ({:?})",
+            adapter_info.name, adapter_info.backend
+        );
+
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("shader playground"),
+            required_limits:
+                wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+            ..Default::default()
+        }))
+        .with_context(|| "requesting a wgpu device")?;
+        // errors outside of loading a shader are logged instead of panicking
+        device.on_uncaptured_error(Box::new(|err| error!("wgpu error: {}", err)));
+
+        let vertex = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("full screen triangle"),
+            source: wgpu::ShaderSource::Wgsl(VERTEX_SHADER.into()),
+        });
+
+        let uniforms = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("uniforms"),
+            contents: &[0; UNIFORM_STRIDE as usize * UNIFORM_DECLARATIONS.len()],
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let layout_entries =
+            UNIFORM_DECLARATIONS.map(|(_, binding, _)| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("uniforms"),
+            entries: &layout_entries,
+        });
+        let group_entries = UNIFORM_DECLARATIONS.map(|(_, binding, _)| wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &uniforms,
+                offset: binding as u64 * UNIFORM_STRIDE,
+                size: None,
+            }),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("uniforms"),
+            layout: &bind_group_layout,
+            entries: &group_entries,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("fragment shader"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Ok(Self {
+            device,
+            queue,
+            vertex,
+            uniforms,
+            bind_group,
+            pipeline_layout,
+            pipeline: None,
+            target: None,
+        })
+    }
+
+    /// The color texture and readback buffer for `width` by `height`,
+    /// recreated when the resolution changed.
+    fn target(&mut self, width: u32, height: u32) -> &(wgpu::Texture, wgpu::Buffer) {
+        if self
+            .target
+            .as_ref()
+            .is_some_and(|(texture, _)| texture.width() != width || texture.height() != height)
+        {
+            self.target = None;
+        }
+        self.target.get_or_insert_with(|| {
+            let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("render target"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            });
+            let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("readback"),
+                size: padded_row_len(width) as u64 * height as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            (texture, readback)
+        })
+    }
+
+    fn load(&mut self, source: &str) -> shader::Result<()> {
+        let source = declare_uniforms(source);
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("fragment shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        let compilation_info = pollster::block_on(module.get_compilation_info());
+        let pipeline = self
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("fragment shader"),
+                layout: Some(&self.pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &self.vertex,
+                    entry_point: None,
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &module,
+                    entry_point: None,
+                    compilation_options: Default::default(),
+                    targets: &[Some(FORMAT.into())],
+                }),
+                multiview: None,
+                cache: None,
+            });
+        let error = pollster::block_on(self.device.pop_error_scope());
+
+        let log = compilation_info
+            .messages
+            .iter()
+            .filter(|message| message.message_type == wgpu::CompilationMessageType::Error)
+            // the message points at the code itself
+            .map(|message| message.message.trim())
+            .collect::<Vec<_>>();
+        if !log.is_empty() {
+            return Err(shader::Error::ShaderCompilationError {
+                stage: Stage::Fragment,
+                log: log.join("\n"),
+            });
+        }
+        if let Some(err) = error {
+            return Err(shader::Error::LinkError(err.to_string()));
+        }
+
+        self.pipeline = Some(pipeline);
+        Ok(())
+    }
+
+    fn render_image(&mut self, inputs: FrameInputs) -> anyhow::Result<Image> {
+        let (width, height) = inputs.resolution;
+        let values: [&[f32]; 3] = [
+            &[width as f32, height as f32],
+            &[inputs.mouse.0, inputs.mouse.1],
+            &[inputs.time],
+        ];
+        for ((_, binding, _), value) in UNIFORM_DECLARATIONS.iter().zip(values) {
+            let bytes = value
+                .iter()
+                .flat_map(|value| value.to_ne_bytes())
+                .collect::<Vec<_>>();
+            self.queue
+                .write_buffer(&self.uniforms, *binding as u64 * UNIFORM_STRIDE, &bytes);
+        }
+
+        self.target(width, height);
+        let (texture, readback) = self.target.as_ref().unwrap();
+        let view = texture.create_view(&Default::default());
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("fragment shader"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                ..Default::default()
+            });
+            if let Some(pipeline) = &self.pipeline {
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, &self.bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: readback,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_len(width)),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device
+            .poll(wgpu::PollType::Wait)
+            .with_context(|| "waiting for the frame")?;
+        receiver
+            .recv()
+            .map_err(|_| anyhow!("the readback buffer was not mapped"))?
+            .with_context(|| "mapping the readback buffer")?;
+
+        // the rows are padded, and start from the top like the image's
+        let row_len = width as usize * 4;
+        let pixels = slice
+            .get_mapped_range()
+            .chunks_exact(padded_row_len(width) as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+        readback.unmap();
+
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+/// Appends a declaration for every playground uniform the shader uses without
+/// declaring it. Declarations in WGSL can follow their use, so the line
+/// numbers of the user's code stay intact.
+pub(crate) fn declare_uniforms(source: &str) -> String {
+    let mut source = source.to_string();
+    for (name, binding, ty) in UNIFORM_DECLARATIONS {
+        let occurrences = identifiers(&source, name).collect::<Vec<_>>();
+        if !occurrences.is_empty() && !occurrences.contains(&Identifier::Declaration) {
+            source.push_str(&format!(
+                "\n@group(0) @binding({}) var<uniform> {}: {};",
+                binding, name, ty
+            ));
+        }
+    }
+    source
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Identifier {
+    /// A `var`, `let`, `const` or `override` named like the identifier, at
+    /// module scope or in a function, or a parameter or member.
+    Declaration,
+    Use,
+}
+
+/// The occurrences of the identifier `name` outside of comments. Member
+/// accesses like `in.uTime` are skipped.
+fn identifiers<'a>(source: &'a str, name: &'a str) -> impl Iterator<Item = Identifier> + 'a {
+    let mut offset = 0;
+    std::iter::from_fn(move || loop {
+        let start = offset + find_call(&source[offset..], name)?;
+        offset = start + name.len();
+
+        let before = source[..start].trim_end();
+        if before.ends_with('.') {
+            continue;
+        }
+        // skip the address space and access mode of `var<uniform>`
+        let before = match before.strip_suffix('>') {
+            Some(rest) => rest
+                .rfind('<')
+                .map_or(before, |open| rest[..open].trim_end()),
+            None => before,
+        };
+        let keyword = before
+            .rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or_default();
+        let after = source[offset..].trim_start();
+        let typed = after.starts_with(':') && !after.starts_with("::");
+        return Some(
+            if typed || matches!(keyword, "var" | "let" | "const" | "override") {
+                Identifier::Declaration
+            } else {
+                Identifier::Use
+            },
+        );
+    })
+}
+
+/// Rows copied out of a texture must be aligned to 256 bytes.
+fn padded_row_len(width: u32) -> u32 {
+    (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAIN: &str = "@fragment\nfn main() -> @location(0) vec4<f32> {\n";
+
+    fn declarations(source: &str) -> Vec<&str> {
+        source
+            .lines()
+            .filter(|line| line.starts_with("@group(0) @binding("))
+            .collect()
+    }
+
+    #[test]
+    fn declares_used_uniforms() {
+        let source =
+            format!("{MAIN}    return vec4<f32>(sin(uTime), uMouse / uResolution, 1.0);\n}}");
+        let declared = declare_uniforms(&source);
+        assert!(declared.starts_with(&source));
+        assert_eq!(
+            declarations(&declared),
+            [
+                "@group(0) @binding(0) var<uniform> uResolution: vec2<f32>;",
+                "@group(0) @binding(1) var<uniform> uMouse: vec2<f32>;",
+                "@group(0) @binding(2) var<uniform> uTime: f32;",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_uniforms_declared_by_the_shader() {
+        let source = format!(
+            "@group(0) @binding(2)\nvar<uniform>\n    uTime: f32;\n{MAIN}    return vec4<f32>(uTime);\n}}"
+        );
+        assert_eq!(declare_uniforms(&source), source);
+    }
+
+    #[test]
+    fn skips_locals_parameters_and_members() {
+        for body in [
+            "    let uTime = 1.0;\n    return vec4<f32>(uTime);\n}",
+            "    var uTime: f32 = 1.0;\n    return vec4<f32>(uTime);\n}",
+            "    return vec4<f32>(in.uTime);\n}",
+            "    // uses uTime\n    return vec4<f32>(1.0);\n}",
+            "    return vec4<f32>(f(1.0));\n}\nfn f(uTime: f32) -> f32 { return uTime; }",
+        ] {
+            let source = format!("{MAIN}{body}");
+            assert_eq!(declare_uniforms(&source), source, "{}", body);
+        }
+        let source = "const uTime = 1.0;\noverride uMouse: vec2<f32>;";
+        assert_eq!(declare_uniforms(source), source);
+    }
+}
@@ -0,0 +1,35 @@
+#version 330
+
+out vec4 oColor;
+
+in vec2 iUv;
+
+uniform sampler2D uTextureA;
+uniform sampler2D uTextureB;
+
+// 0: wipe, 1: difference
+uniform int uMode;
+
+// the horizontal position of the wipe in uv space, A is shown on the left
+uniform float uWipe;
+
+// the factor the absolute difference is multiplied by
+uniform float uGain;
+
+void main() {
+    vec4 a = texture(uTextureA, iUv);
+    vec4 b = texture(uTextureB, iUv);
+
+    if (uMode == 1) {
+        oColor = vec4(abs(a.rgb - b.rgb) * uGain, 1.0);
+        return;
+    }
+
+    // a line one pixel wide marks the wipe
+    float pixel = 1.0 / float(textureSize(uTextureA, 0).x);
+    if (abs(iUv.x - uWipe) < pixel) {
+        oColor = vec4(1.0);
+        return;
+    }
+    oColor = iUv.x < uWipe ? a : b;
+}
@@ -0,0 +1,43 @@
+#version 430
+
+out vec4 oColor;
+
+// the float render target of the pass being diagnosed
+uniform sampler2D uTexture;
+
+// the number of pixels in each category
+layout(std430, binding = 0) buffer Counts {
+    uint nanCount;
+    uint infCount;
+    uint negativeCount;
+    uint overOneCount;
+};
+
+const vec3 NAN_COLOR = vec3(1.0, 0.0, 1.0);
+const vec3 INF_COLOR = vec3(0.0, 1.0, 1.0);
+const vec3 NEGATIVE_COLOR = vec3(0.0, 0.2, 1.0);
+const vec3 OVER_ONE_COLOR = vec3(1.0, 0.6, 0.0);
+
+void main() {
+    vec4 value = texelFetch(uTexture, ivec2(gl_FragCoord.xy), 0);
+
+    vec3 color;
+    if (any(isnan(value))) {
+        atomicAdd(nanCount, 1u);
+        color = NAN_COLOR;
+    } else if (any(isinf(value))) {
+        atomicAdd(infCount, 1u);
+        color = INF_COLOR;
+    } else if (any(lessThan(value, vec4(0.0)))) {
+        atomicAdd(negativeCount, 1u);
+        color = NEGATIVE_COLOR;
+    } else if (any(greaterThan(value, vec4(1.0)))) {
+        atomicAdd(overOneCount, 1u);
+        color = OVER_ONE_COLOR;
+    } else {
+        // dim valid pixels so the highlighted ones stand out
+        color = vec3(dot(value.rgb, vec3(0.2126, 0.7152, 0.0722)) * 0.35);
+    }
+
+    oColor = vec4(color, 1.0);
+}
@@ -0,0 +1,49 @@
+#version 430
+
+out vec4 oColor;
+
+in vec2 iUv;
+
+// written by `PROFILE_COUNT()` in the profiled shader
+layout(std430, binding = 2) buffer ProfileCounts {
+    uint minCount;
+    uint maxCount;
+    uint counts[];
+};
+
+// the width of the counter grid
+uniform int uWidth;
+
+// the min and max counts of the previous frame
+uniform vec2 uRange;
+
+// draw the color scale from left to right instead of the counts
+uniform bool uLegend;
+
+vec3 heat(float t) {
+    const vec3 stops[5] = vec3[](
+        vec3(0.0, 0.0, 0.4),
+        vec3(0.0, 0.4, 1.0),
+        vec3(0.0, 0.9, 0.3),
+        vec3(1.0, 0.9, 0.0),
+        vec3(1.0, 0.0, 0.0)
+    );
+    float x = clamp(t, 0.0, 1.0) * 4.0;
+    int i = min(int(x), 3);
+    return mix(stops[i], stops[i + 1], x - float(i));
+}
+
+void main() {
+    if (uLegend) {
+        oColor = vec4(heat(iUv.x), 1.0);
+        return;
+    }
+
+    ivec2 p = ivec2(gl_FragCoord.xy);
+    uint count = counts[p.y * uWidth + p.x];
+    atomicMin(minCount, count);
+    atomicMax(maxCount, count);
+
+    float t = (float(count) - uRange.x) / max(uRange.y - uRange.x, 1.0);
+    oColor = vec4(heat(t), 1.0);
+}
@@ -0,0 +1,15 @@
+#version 330
+
+out vec4 oColor;
+
+in vec2 iUv;
+
+// the offscreen render target
+uniform sampler2D uTexture;
+
+// the region of the texture to draw: offset (xy) and size (zw) in uv space
+uniform vec4 uUvRect;
+
+void main() {
+    oColor = texture(uTexture, uUvRect.xy + iUv * uUvRect.zw);
+}
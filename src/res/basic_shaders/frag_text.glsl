@@ -0,0 +1,46 @@
+#version 330
+
+out vec4 oColor;
+
+in vec2 iUv;
+
+// one texel per character, one row per line
+uniform usampler2D uText;
+
+// the glyphs of the printable ascii characters, side by side
+uniform sampler2D uFont;
+
+// the size of the overlay in pixels
+uniform vec2 uSize;
+
+// the size of a character cell in font pixels
+uniform ivec2 uCell;
+
+// the number of screen pixels per font pixel
+uniform int uScale;
+
+// the number of font pixels around the text
+uniform int uPadding;
+
+uniform vec4 uBackground;
+uniform vec4 uForeground;
+
+void main() {
+    // font pixel, measured from the top left
+    ivec2 p = ivec2(iUv.x * uSize.x, (1.0 - iUv.y) * uSize.y) / uScale - uPadding;
+
+    float on = 0.0;
+    ivec2 lines = textureSize(uText, 0);
+    if (p.x >= 0 && p.y >= 0) {
+        ivec2 cell = p / uCell;
+        ivec2 glyph = p % uCell;
+        if (cell.x < lines.x && cell.y < lines.y && glyph.x < uCell.x - 1 && glyph.y < uCell.y - 2) {
+            uint c = texelFetch(uText, cell, 0).r;
+            if (c >= 32u && c < 127u) {
+                on = texelFetch(uFont, ivec2(int(c - 32u) * (uCell.x - 1) + glyph.x, glyph.y), 0).r;
+            }
+        }
+    }
+
+    oColor = mix(uBackground, uForeground, on);
+}
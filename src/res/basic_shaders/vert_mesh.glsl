@@ -0,0 +1,22 @@
+#version 330
+
+layout(location = 0) in vec3 aPosition;
+layout(location = 1) in vec3 aNormal;
+layout(location = 2) in vec2 aUv;
+
+uniform mat4 uModel;
+uniform mat4 uView;
+uniform mat4 uProjection;
+
+out vec2 iUv;
+// world space
+out vec3 iPosition;
+out vec3 iNormal;
+
+void main() {
+    vec4 position = uModel * vec4(aPosition, 1.0);
+    gl_Position = uProjection * uView * position;
+    iUv = aUv;
+    iPosition = position.xyz;
+    iNormal = mat3(transpose(inverse(uModel))) * aNormal;
+}
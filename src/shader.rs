@@ -1,97 +1,627 @@
-use std::ffi::CString;
+use std::{
+    borrow::Cow,
+    cell::{Cell, OnceCell, RefCell},
+    ffi::{CStr, CString},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread::JoinHandle,
+};
 
+use anyhow::anyhow;
+
+use log::warn;
+
+use crate::compat;
 use crate::gl;
+use crate::program_cache::ProgramCache;
 use gl::types::*;
 
 pub struct ShaderProgram(pub u32);
 
+/// A programmable stage of the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl Stage {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Self::Vertex => gl::VERTEX_SHADER,
+            Self::TessControl => gl::TESS_CONTROL_SHADER,
+            Self::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            Self::Geometry => gl::GEOMETRY_SHADER,
+            Self::Fragment => gl::FRAGMENT_SHADER,
+            Self::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Vertex => "vertex",
+            Self::TessControl => "tessellation control",
+            Self::TessEvaluation => "tessellation evaluation",
+            Self::Geometry => "geometry",
+            Self::Fragment => "fragment",
+            Self::Compute => "compute",
+        })
+    }
+}
+
 #[derive(Debug, Clone, thiserror::Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
-    #[error("Could not compile shader.\n{0}")]
-    ShaderCompilationError(String),
+    #[error("Could not compile the {stage} shader.\n{log}")]
+    ShaderCompilationError { stage: Stage, log: String },
+    #[error("Could not link the shader program.\n{0}")]
+    LinkError(String),
+    #[error("Could not preprocess shader.\n{0}")]
+    PreprocessingError(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 fn get_program_info_log(program: u32) -> String {
-    let mut info_log = Vec::with_capacity(512);
+    let mut log_len = 0;
     unsafe {
-        info_log.set_len(512); // subtract 1 to skip the trailing null character
+        gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut log_len);
     }
-
+    let mut info_log = vec![0u8; log_len.max(1) as usize];
     unsafe {
         gl::GetProgramInfoLog(
             program,
-            512,
-            std::ptr::null_mut(),
+            info_log.len() as _,
+            &mut log_len,
             info_log.as_mut_ptr() as *mut GLchar,
         );
     }
-
-    std::str::from_utf8(&info_log).unwrap().to_string()
+    info_log.truncate(log_len as _);
+    String::from_utf8_lossy(&info_log).into_owned()
 }
 
 fn get_shader_info_log(shader: u32) -> String {
-    let mut info_log = Vec::with_capacity(512);
     let mut log_len = 0;
+    unsafe {
+        gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut log_len);
+    }
+    let mut info_log = vec![0u8; log_len.max(1) as usize];
     unsafe {
         gl::GetShaderInfoLog(
             shader,
-            512,
+            info_log.len() as _,
             &mut log_len,
             info_log.as_mut_ptr() as *mut GLchar,
         );
-        info_log.set_len(log_len as _);
     }
-    std::str::from_utf8(&info_log).unwrap().to_string()
+    info_log.truncate(log_len as _);
+    String::from_utf8_lossy(&info_log).into_owned()
 }
 
-impl ShaderProgram {
-    pub fn new(vertex_shader_source: &str, fragment_shader_source: &str) -> Result<Self> {
+/// How programs are compiled off the main thread, see [`enable_parallel_compile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParallelCompile {
+    /// On the driver's threads, with `GL_KHR_parallel_shader_compile`.
+    Driver,
+    /// On the thread of [`spawn_compile_worker`].
+    Worker,
+}
+
+thread_local! {
+    static PARALLEL_COMPILE: Cell<Option<ParallelCompile>> = const { Cell::new(None) };
+    static COMPILE_WORKER: RefCell<Option<CompileWorker>> = const { RefCell::new(None) };
+}
+
+/// Whether the driver supports `GL_KHR_parallel_shader_compile`.
+pub fn supports_parallel_compile() -> bool {
+    extensions().any(|name| {
+        name == "GL_KHR_parallel_shader_compile" || name == "GL_ARB_parallel_shader_compile"
+    })
+}
+
+/// Compiles the programs of [`ShaderProgramBuilder::start_cached`] off the
+/// main thread so [`PendingProgram::is_ready`] can be polled instead of
+/// waiting: on the driver's threads if it supports
+/// `GL_KHR_parallel_shader_compile`, else on the thread of
+/// [`spawn_compile_worker`] if one was started.
+pub fn enable_parallel_compile() -> Option<ParallelCompile> {
+    let mode = if supports_parallel_compile() {
         unsafe {
-            // vertex shader
-            let vertex_shader = gl::CreateShader(gl::VERTEX_SHADER);
-            let c_str_vert = CString::new(vertex_shader_source.as_bytes()).unwrap();
-            gl::ShaderSource(vertex_shader, 1, &c_str_vert.as_ptr(), std::ptr::null());
-            gl::CompileShader(vertex_shader);
-
-            let mut success = gl::FALSE as GLint;
-            gl::GetShaderiv(vertex_shader, gl::COMPILE_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                let info_log = get_shader_info_log(vertex_shader);
-                return Err(Error::ShaderCompilationError(info_log));
+            // let the driver pick the number of threads
+            gl::MaxShaderCompilerThreadsKHR(u32::MAX);
+        }
+        Some(ParallelCompile::Driver)
+    } else {
+        COMPILE_WORKER
+            .with_borrow(Option::is_some)
+            .then_some(ParallelCompile::Worker)
+    };
+    PARALLEL_COMPILE.set(mode);
+    mode
+}
+
+/// Starts a thread to compile programs on for drivers without
+/// `GL_KHR_parallel_shader_compile`. `make_current` runs on that thread and
+/// makes a context sharing its objects with the current one current there.
+/// What it returns is kept until another worker is started on this thread.
+pub fn spawn_compile_worker<C>(
+    make_current: impl FnOnce() -> anyhow::Result<C> + Send + 'static,
+) -> anyhow::Result<()> {
+    let (jobs, receiver) = mpsc::channel();
+    let (started, status) = mpsc::sync_channel(1);
+    let thread = std::thread::Builder::new()
+        .name("shader compiler".into())
+        .spawn(move || {
+            let _context = match make_current() {
+                Ok(context) => context,
+                Err(err) => {
+                    let _ = started.send(Err(err));
+                    return;
+                }
+            };
+            let _ = started.send(Ok(()));
+            detect_api();
+            compile_jobs(receiver);
+        })?;
+    status
+        .recv()
+        .unwrap_or_else(|_| Err(anyhow!("the shader compiler thread panicked")))?;
+    COMPILE_WORKER.set(Some(CompileWorker {
+        jobs: Some(jobs),
+        thread: Some(thread),
+    }));
+    Ok(())
+}
+
+/// The stages of a program to compile on the worker thread.
+struct Job {
+    stages: Vec<(Stage, String)>,
+    reply: Sender<Result<ShaderProgram>>,
+}
+
+struct CompileWorker {
+    jobs: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CompileWorker {
+    fn submit(&self, stages: &[(Stage, &str)]) -> Option<Receiver<Result<ShaderProgram>>> {
+        let (reply, receiver) = mpsc::channel();
+        let stages = stages
+            .iter()
+            .map(|&(stage, source)| (stage, source.to_owned()))
+            .collect();
+        self.jobs.as_ref()?.send(Job { stages, reply }).ok()?;
+        Some(receiver)
+    }
+}
+
+impl Drop for CompileWorker {
+    fn drop(&mut self) {
+        // closing the queue stops the thread
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn compile_jobs(jobs: Receiver<Job>) {
+    for job in jobs {
+        let builder = ShaderProgramBuilder {
+            stages: job
+                .stages
+                .iter()
+                .map(|(stage, source)| (*stage, source.as_str()))
+                .collect(),
+        };
+        let program = builder.start(true).finish();
+        unsafe {
+            // the program is used by the main context once this returns
+            gl::Finish();
+        }
+        // the pending program may have been dropped in the meantime
+        let _ = job.reply.send(program);
+    }
+}
+
+pub(crate) fn extensions() -> impl Iterator<Item = String> {
+    let mut count = 0;
+    unsafe {
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+    }
+    (0..count.max(0) as u32).filter_map(|index| unsafe {
+        let name = gl::GetStringi(gl::EXTENSIONS, index);
+        (!name.is_null()).then(|| CStr::from_ptr(name.cast()).to_string_lossy().into_owned())
+    })
+}
+
+thread_local! {
+    static IS_GLES: Cell<Option<bool>> = const { Cell::new(None) };
+}
+
+/// Whether the current context is OpenGL ES. Queried once per context, see
+/// [`detect_api`].
+pub fn is_gles() -> bool {
+    IS_GLES.get().unwrap_or_else(detect_api)
+}
+
+/// Queries the API of the context just made current on this thread for
+/// [`is_gles`]. Called once its functions are loaded.
+pub fn detect_api() -> bool {
+    let gles = unsafe {
+        let version = gl::GetString(gl::VERSION);
+        !version.is_null()
+            && CStr::from_ptr(version.cast())
+                .to_bytes()
+                .starts_with(b"OpenGL ES")
+    };
+    IS_GLES.set(Some(gles));
+    gles
+}
+
+/// The `#version` number of `source` and whether it is GLSL ES.
+fn declared_version(source: &str) -> Option<(u32, bool)> {
+    source.lines().find_map(|line| {
+        let mut words = line.trim().strip_prefix("#version")?.split_whitespace();
+        let number = words.next()?.parse::<u32>().ok()?;
+        Some((number, words.next() == Some("es")))
+    })
+}
+
+fn is_ssbo_extension(line: &str) -> bool {
+    line.trim()
+        .starts_with("#extension GL_ARB_shader_storage_buffer_object")
+}
+
+/// The closest GLSL ES version to the one `source` was written for. Storage
+/// buffers need at least GLSL ES 310.
+fn gles_version(source: &str) -> u32 {
+    let version = match declared_version(source) {
+        Some((number, true)) => number,
+        None | Some((..=330, false)) => 300,
+        Some((..=430, false)) => 310,
+        Some(_) => 320,
+    };
+    if source.lines().any(is_ssbo_extension) {
+        version.max(310)
+    } else {
+        version
+    }
+}
+
+/// Rewrites the `#version` of `source` to GLSL ES `version`, so the built-in
+/// shaders run on ES contexts. Desktop shaders get default precisions, and
+/// the storage buffer extension is dropped since it is core in GLSL ES 310.
+fn gles_source(source: &str, version: u32) -> Cow<'_, str> {
+    let declared = declared_version(source);
+    if declared == Some((version, true)) && !source.lines().any(is_ssbo_extension) {
+        return Cow::Borrowed(source);
+    }
+
+    let lines = source.lines().collect::<Vec<_>>();
+    let header_len = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            !(line.is_empty()
+                || line.starts_with("//")
+                || line.starts_with("#version")
+                || line.starts_with("#extension"))
+        })
+        .unwrap_or(lines.len());
+
+    let mut out = format!("#version {} es\n", version);
+    for line in &lines[..header_len] {
+        if !line.trim().starts_with("#version") && !is_ssbo_extension(line) {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    if !declared.is_some_and(|(_, es)| es) {
+        out.push_str(
+            "precision highp float;\nprecision highp int;\n\
+             precision highp sampler2D;\nprecision highp usampler2D;\n",
+        );
+        if version >= 310 {
+            out.push_str("precision highp image2D;\n");
+        }
+    }
+    out.push_str(&format!("#line {}\n", header_len + 1));
+    for line in &lines[header_len..] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    Cow::Owned(out)
+}
+
+fn submit_shader(stage: Stage, source: &str) -> u32 {
+    unsafe {
+        let shader = gl::CreateShader(stage.gl_enum());
+        let c_str = CString::new(source.as_bytes()).unwrap();
+        gl::ShaderSource(shader, 1, &c_str.as_ptr(), std::ptr::null());
+        gl::CompileShader(shader);
+        shader
+    }
+}
+
+fn check_shader(stage: Stage, shader: u32) -> Result<()> {
+    let mut success = gl::FALSE as GLint;
+    unsafe {
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+    }
+    let info_log = get_shader_info_log(shader);
+    if success != gl::TRUE as GLint {
+        return Err(Error::ShaderCompilationError {
+            stage,
+            log: info_log,
+        });
+    }
+    if !info_log.trim().is_empty() {
+        warn!("{} shader: {}", stage, info_log.trim());
+    }
+    Ok(())
+}
+
+/// Collects the stages of a program. Any combination the driver can link
+/// is allowed, e.g. vertex, tessellation, geometry and fragment stages, or a
+/// single compute stage.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderProgramBuilder<'a> {
+    stages: Vec<(Stage, &'a str)>,
+}
+
+impl<'a> ShaderProgramBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a stage, replacing an earlier source for the same stage.
+    pub fn stage(mut self, stage: Stage, source: &'a str) -> Self {
+        self.stages.retain(|(s, _)| *s != stage);
+        self.stages.push((stage, source));
+        self
+    }
+
+    pub fn vertex(self, source: &'a str) -> Self {
+        self.stage(Stage::Vertex, source)
+    }
+
+    pub fn fragment(self, source: &'a str) -> Self {
+        self.stage(Stage::Fragment, source)
+    }
+
+    pub fn compute(self, source: &'a str) -> Self {
+        self.stage(Stage::Compute, source)
+    }
+
+    /// Compiles every stage and links them. Fails with the log of the first
+    /// stage that does not compile.
+    pub fn build(self) -> Result<ShaderProgram> {
+        self.start(false).finish()
+    }
+
+    /// Like [`Self::build`], but reuses the binary of an identical program
+    /// from `cache` and stores newly linked programs in it.
+    pub fn build_cached(self, cache: &mut ProgramCache) -> Result<ShaderProgram> {
+        self.start_cached(cache).finish_cached(cache)
+    }
+
+    /// Like [`Self::build_cached`], but returns as soon as the stages are
+    /// handed to the driver. A cached program is ready right away.
+    pub fn start_cached(self, cache: &mut ProgramCache) -> PendingProgram {
+        let key = cache.key(&self.stages);
+        match cache.load(&key) {
+            Some(program) => PendingProgram {
+                program: std::mem::ManuallyDrop::new(program).0,
+                shaders: Vec::new(),
+                cache_key: None,
+                worker: None,
+            },
+            None => {
+                let mut pending = self.start_background();
+                pending.cache_key = Some(key);
+                pending
             }
+        }
+    }
+
+    /// Hands the stages to the worker thread if it is enabled, else to the
+    /// driver.
+    fn start_background(self) -> PendingProgram {
+        if PARALLEL_COMPILE.get() == Some(ParallelCompile::Worker) {
+            let reply = COMPILE_WORKER.with_borrow(|worker| worker.as_ref()?.submit(&self.stages));
+            if let Some(reply) = reply {
+                return PendingProgram {
+                    program: 0,
+                    shaders: Vec::new(),
+                    cache_key: None,
+                    worker: Some(WorkerProgram {
+                        reply,
+                        program: OnceCell::new(),
+                    }),
+                };
+            }
+        }
+        self.start(true)
+    }
+
+    fn start(self, retrievable: bool) -> PendingProgram {
+        // every stage of an ES program has to use the same version
+        let gles = is_gles().then(|| {
+            self.stages
+                .iter()
+                .map(|(_, source)| gles_version(source))
+                .max()
+                .unwrap_or(300)
+        });
+        let shaders = self
+            .stages
+            .iter()
+            .map(|&(stage, source)| {
+                let source = match gles {
+                    Some(version) => gles_source(source, version),
+                    None => Cow::Borrowed(source),
+                };
+                (stage, submit_shader(stage, &source))
+            })
+            .collect::<Vec<_>>();
+        unsafe {
+            // link
+            let program = gl::CreateProgram();
+            if retrievable {
+                gl::ProgramParameteri(program, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as _);
+            }
+            for &(_, shader) in &shaders {
+                gl::AttachShader(program, shader);
+            }
+            gl::LinkProgram(program);
+            PendingProgram {
+                program,
+                shaders,
+                cache_key: None,
+                worker: None,
+            }
+        }
+    }
+}
+
+/// A program whose stages were handed to the driver but whose status was not
+/// queried yet. With [`enable_parallel_compile`] it is compiled in the
+/// background, otherwise it is done by the time this is returned. Dropping
+/// it deletes the program.
+pub struct PendingProgram {
+    program: u32,
+    /// Empty if the program was loaded from a cache.
+    shaders: Vec<(Stage, u32)>,
+    /// The key to store the linked program under, if it was not cached yet.
+    cache_key: Option<String>,
+    /// Set if the program is compiled on the worker thread instead.
+    worker: Option<WorkerProgram>,
+}
 
-            // fragment shader
-            let fragment_shader = gl::CreateShader(gl::FRAGMENT_SHADER);
-            let c_str_frag = CString::new(fragment_shader_source.as_bytes()).unwrap();
-            gl::ShaderSource(fragment_shader, 1, &c_str_frag.as_ptr(), std::ptr::null());
-            gl::CompileShader(fragment_shader);
+struct WorkerProgram {
+    reply: Receiver<Result<ShaderProgram>>,
+    /// The reply once [`PendingProgram::is_ready`] received it.
+    program: OnceCell<Result<ShaderProgram>>,
+}
 
-            gl::GetShaderiv(fragment_shader, gl::COMPILE_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                let info_log = get_shader_info_log(fragment_shader);
-                return Err(Error::ShaderCompilationError(info_log));
+impl WorkerProgram {
+    fn is_ready(&self) -> bool {
+        if self.program.get().is_some() {
+            return true;
+        }
+        match self.reply.try_recv() {
+            Ok(program) => {
+                let _ = self.program.set(program);
+                true
             }
+            Err(TryRecvError::Empty) => false,
+            Err(TryRecvError::Disconnected) => true,
+        }
+    }
 
-            // link shaders
-            let shader_program = gl::CreateProgram();
-            gl::AttachShader(shader_program, vertex_shader);
-            gl::AttachShader(shader_program, fragment_shader);
-            gl::LinkProgram(shader_program);
+    fn finish(self) -> Result<ShaderProgram> {
+        match self.program.into_inner() {
+            Some(program) => program,
+            None => self.reply.recv().unwrap_or_else(|_| {
+                Err(Error::LinkError(
+                    "The shader compiler thread stopped.".to_owned(),
+                ))
+            }),
+        }
+    }
+}
+
+impl PendingProgram {
+    /// Whether [`Self::finish`] would return without waiting for the driver.
+    pub fn is_ready(&self) -> bool {
+        if let Some(worker) = &self.worker {
+            return worker.is_ready();
+        }
+        if PARALLEL_COMPILE.get() != Some(ParallelCompile::Driver) {
+            return true;
+        }
+        let mut done = gl::FALSE as GLint;
+        unsafe {
+            gl::GetProgramiv(self.program, gl::COMPLETION_STATUS_KHR, &mut done);
+        }
+        done == gl::TRUE as GLint
+    }
+
+    /// Waits for the program to link. Fails with the log of the first stage
+    /// that does not compile.
+    pub fn finish(mut self) -> Result<ShaderProgram> {
+        if let Some(worker) = self.worker.take() {
+            return worker.finish();
+        }
+        for &(stage, shader) in &self.shaders {
+            check_shader(stage, shader)?;
+        }
+
+        let mut success = gl::FALSE as GLint;
+        unsafe {
+            gl::GetProgramiv(self.program, gl::LINK_STATUS, &mut success);
+        }
+        if success != gl::TRUE as GLint {
+            return Err(Error::LinkError(get_program_info_log(self.program)));
+        }
+
+        self.delete_shaders();
+        Ok(ShaderProgram(std::mem::take(&mut self.program)))
+    }
+
+    /// Like [`Self::finish`], and stores a newly linked program in `cache`.
+    pub fn finish_cached(mut self, cache: &mut ProgramCache) -> Result<ShaderProgram> {
+        let key = self.cache_key.take();
+        let program = self.finish()?;
+        if let Some(key) = key {
+            cache.store(&key, &program);
+        }
+        Ok(program)
+    }
+
+    fn delete_shaders(&mut self) {
+        for (_, shader) in self.shaders.drain(..) {
+            unsafe {
+                gl::DetachShader(self.program, shader);
+                gl::DeleteShader(shader);
+            }
+        }
+    }
+}
 
-            gl::GetProgramiv(shader_program, gl::LINK_STATUS, &mut success);
-            if success != gl::TRUE as GLint {
-                let info_log = get_program_info_log(shader_program);
-                return Err(Error::ShaderCompilationError(info_log));
+impl Drop for PendingProgram {
+    fn drop(&mut self) {
+        self.delete_shaders();
+        if self.program != 0 {
+            unsafe {
+                gl::DeleteProgram(self.program);
             }
+        }
+    }
+}
 
-            // cleanup
-            gl::DeleteShader(vertex_shader);
-            gl::DeleteShader(fragment_shader);
+impl ShaderProgram {
+    pub fn new(vertex_shader_source: &str, fragment_shader_source: &str) -> Result<Self> {
+        ShaderProgramBuilder::new()
+            .vertex(vertex_shader_source)
+            .fragment(fragment_shader_source)
+            .build()
+    }
 
-            Ok(Self(shader_program))
+    /// The local work group size declared by a compute shader.
+    pub fn compute_work_group_size(&self) -> [u32; 3] {
+        let mut size = [0; 3];
+        unsafe {
+            gl::GetProgramiv(self.0, gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
         }
+        size.map(|n| n.max(1) as u32)
     }
 
     pub fn uniform_location(&self, name: &str) -> i32 {
@@ -101,6 +631,22 @@ impl ShaderProgram {
         unsafe { gl::GetUniformLocation(self.0, p) }
     }
 
+    /// Binds the storage block `name` to `binding`. Returns `false` if the
+    /// program has no such block, or on ES where the binding can only be set
+    /// in the shader.
+    pub fn storage_block_binding(&self, name: &str, binding: u32) -> bool {
+        let name = CString::new(name).expect("Failed to create CString");
+        compat::storage_block_binding(self.0, &name, binding)
+    }
+
+    /// Points the sampler or image uniform `name` at texture or image `unit`.
+    /// The program has to be in use. Images are skipped on ES, where their
+    /// unit can only be set in the shader.
+    pub fn uniform_unit(&self, name: &str, unit: i32) {
+        let name = CString::new(name).expect("Failed to create CString");
+        compat::uniform_unit(self.0, &name, unit);
+    }
+
     pub fn use_program(&self) {
         unsafe {
             gl::UseProgram(self.0);
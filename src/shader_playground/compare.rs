@@ -0,0 +1,364 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use log::{error, info};
+
+use crate::{
+    gl,
+    plane_buffer::PlaneBuffer,
+    program_cache::ProgramCache,
+    render_target::{Filter, Format, Presenter, RenderTarget},
+    shader::{ShaderProgram, Stage},
+};
+
+use super::{
+    debug_printf::DebugPrintf,
+    normalize::{self, Dialect},
+    preprocessor,
+    reloaded_shader::ReloadedShader,
+    scene::Scene,
+    uniforms::Uniforms,
+    variants,
+};
+
+const VERTEX_SHADER: &str = include_str!("../res/basic_shaders/vert.glsl");
+const FRAG_SHADER_COMPARE: &str = include_str!("../res/basic_shaders/frag_compare.glsl");
+
+const MIN_GAIN: f32 = 1.0;
+const MAX_GAIN: f32 = 1024.0;
+
+/// Where the second shader of a comparison comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompareSource {
+    File(PathBuf),
+    /// A git revision of the main shader file, e.g. `HEAD~1`.
+    Revision(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareMode {
+    /// Only the main shader is shown.
+    Off,
+    SideBySide,
+    /// The main shader on the left, the other one on the right of a draggable line.
+    Wipe,
+    /// The amplified absolute difference of both shaders.
+    Difference,
+}
+
+impl CompareMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::SideBySide,
+            Self::SideBySide => Self::Wipe,
+            Self::Wipe => Self::Difference,
+            Self::Difference => Self::Off,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Locations {
+    texture_a: i32,
+    texture_b: i32,
+    mode: i32,
+    wipe: i32,
+    gain: i32,
+}
+
+/// Renders a second shader with the same inputs as the main one and shows
+/// both side by side, with a wipe or as their difference.
+pub struct Compare {
+    pub source: CompareSource,
+    shader: ReloadedShader,
+    uniforms: Uniforms,
+    target: Option<RenderTarget>,
+    composite: Option<RenderTarget>,
+
+    program: ShaderProgram,
+    locations: Locations,
+
+    pub mode: CompareMode,
+    /// The position of the wipe, from 0 (left) to 1 (right).
+    pub wipe: f32,
+    /// Whether the wipe follows the mouse.
+    pub dragging: bool,
+    gain: f32,
+    filter: Filter,
+}
+
+impl Compare {
+    pub fn new(source: CompareSource, filter: Filter) -> anyhow::Result<Self> {
+        let program = ShaderProgram::new(VERTEX_SHADER, FRAG_SHADER_COMPARE)?;
+        let locations = Locations {
+            texture_a: program.uniform_location("uTextureA"),
+            texture_b: program.uniform_location("uTextureB"),
+            mode: program.uniform_location("uMode"),
+            wipe: program.uniform_location("uWipe"),
+            gain: program.uniform_location("uGain"),
+        };
+
+        Ok(Self {
+            source,
+            shader: Default::default(),
+            uniforms: Default::default(),
+            target: None,
+            composite: None,
+
+            program,
+            locations,
+
+            mode: CompareMode::SideBySide,
+            wipe: 0.5,
+            dragging: false,
+            gain: 16.0,
+            filter,
+        })
+    }
+
+    /// The file to watch for changes, if the other shader is a file.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.source {
+            CompareSource::File(path) => Some(path),
+            CompareSource::Revision(_) => None,
+        }
+    }
+
+    /// (Re)loads the other shader, linked with the same `stages` and
+    /// `defines` as the main one. A revision is read from the repository
+    /// containing `main_path`.
+    pub fn load(
+        &mut self,
+        main_path: Option<&Path>,
+        stages: &[(Stage, String)],
+        defines: &str,
+        dialect: Option<Dialect>,
+        cache: &mut ProgramCache,
+    ) {
+        let source = match (&self.source, main_path) {
+            (CompareSource::File(path), _) => std::fs::read_to_string(path),
+            (CompareSource::Revision(revision), Some(path)) => read_revision(path, revision),
+            (CompareSource::Revision(_), None) => {
+                self.shader = ReloadedShader::NotProvided;
+                return;
+            }
+        };
+
+        let preprocessed = source.map(|source| {
+            preprocessor::preprocess(&normalize::normalize(&source, Stage::Fragment, dialect))
+        });
+        self.shader = match preprocessed {
+            Err(err) => ReloadedShader::FileReadingError(err),
+            Ok(Err(err)) => ReloadedShader::ShaderError(err),
+            Ok(Ok(preprocessed)) => {
+                let stages = stages
+                    .iter()
+                    .map(|(stage, source)| {
+                        (*stage, variants::inject_after_version(source, defines))
+                    })
+                    .collect::<Vec<_>>();
+                ReloadedShader::from_sources(
+                    &stages,
+                    &variants::inject_after_version(&preprocessed.source, defines),
+                    cache,
+                )
+            }
+        };
+        match &self.shader {
+            ReloadedShader::NotProvided => {}
+            ReloadedShader::Shader(_) => info!("comparison shader successfully loaded"),
+            ReloadedShader::FileReadingError(err) => {
+                error!("could not load the comparison shader. Error: {}", err)
+            }
+            ReloadedShader::ShaderError(err) => {
+                error!("comparison shader compilation error: {}", err)
+            }
+        };
+
+        self.uniforms = self
+            .shader
+            .as_shader()
+            .map(Uniforms::init)
+            .unwrap_or_default();
+
+        // `debugPrintf` and `PROFILE_COUNT()` stay inactive in the other shader
+        if let Some(shader) = self.shader.as_shader() {
+            shader.use_program();
+            DebugPrintf::disable(shader);
+            self.uniforms.bind_profile_width(0);
+        }
+    }
+
+    /// Renders the other shader into a target of the same size and format as `main`.
+    pub fn render(
+        &mut self,
+        main: &RenderTarget,
+        plane: &PlaneBuffer,
+        scene: &Scene,
+        mouse_position: (f32, f32),
+        time: f32,
+    ) -> anyhow::Result<()> {
+        if self.mode == CompareMode::Off {
+            return Ok(());
+        }
+        if matches!(&self.target, Some(target) if target.format != main.format) {
+            self.target = None;
+        }
+        let target = match self.target.take() {
+            Some(mut target) => {
+                target.resize(main.width, main.height)?;
+                target
+            }
+            None => RenderTarget::new(main.width, main.height, main.format, self.filter)?,
+        };
+        let target = &*self.target.insert(target);
+
+        target.bind();
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+
+            if let Some(shader) = self.shader.as_shader() {
+                shader.use_program();
+                self.uniforms.bind(
+                    (main.width as f32, main.height as f32),
+                    mouse_position,
+                    time,
+                );
+                scene.draw(plane, self.uniforms, (main.width, main.height));
+            }
+        }
+        RenderTarget::unbind();
+        Ok(())
+    }
+
+    /// Presents the main and the other shader over `viewport` according to the mode.
+    pub fn present(
+        &mut self,
+        main: &RenderTarget,
+        plane: &PlaneBuffer,
+        presenter: &Presenter,
+        viewport: (i32, i32, i32, i32),
+    ) -> anyhow::Result<()> {
+        if self.mode == CompareMode::Off {
+            presenter.present(main, plane, viewport);
+            return Ok(());
+        }
+        let Some(other) = &self.target else {
+            anyhow::bail!("`Compare::render` was not called");
+        };
+
+        let mode = match self.mode {
+            CompareMode::Off => unreachable!(),
+            CompareMode::SideBySide => {
+                let (x, y, width, height) = viewport;
+                let half = width / 2;
+                let aspect = main.width as f32 / main.height as f32;
+                presenter.present(main, plane, fit((x, y, half, height), aspect));
+                presenter.present(other, plane, fit((x + half, y, half, height), aspect));
+                return Ok(());
+            }
+            CompareMode::Wipe => 0,
+            CompareMode::Difference => 1,
+        };
+
+        let composite = match self.composite.take() {
+            Some(mut composite) => {
+                composite.resize(main.width, main.height)?;
+                composite
+            }
+            None => RenderTarget::new(main.width, main.height, Format::Rgba8, self.filter)?,
+        };
+        let composite = &*self.composite.insert(composite);
+
+        composite.bind();
+        self.program.use_program();
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, main.texture);
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, other.texture);
+
+            let l = self.locations;
+            gl::Uniform1i(l.texture_a, 0);
+            gl::Uniform1i(l.texture_b, 1);
+            gl::Uniform1i(l.mode, mode);
+            gl::Uniform1f(l.wipe, self.wipe);
+            gl::Uniform1f(l.gain, self.gain);
+
+            gl::BindVertexArray(plane.vao);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        RenderTarget::unbind();
+
+        presenter.present(composite, plane, viewport);
+        Ok(())
+    }
+
+    /// Doubles or halves the amplification of the difference.
+    pub fn scale_gain(&mut self, factor: f32) {
+        self.gain = (self.gain * factor).clamp(MIN_GAIN, MAX_GAIN);
+        info!("difference gain: x{}", self.gain);
+    }
+
+    /// A one line description of the comparison for the overlay.
+    pub fn label(&self) -> Option<String> {
+        let source = match &self.source {
+            CompareSource::File(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.display().to_string()),
+            CompareSource::Revision(revision) => format!("@{}", revision),
+        };
+        match self.mode {
+            CompareMode::Off => None,
+            CompareMode::SideBySide => Some(format!("A | B {}", source)),
+            CompareMode::Wipe => Some(format!("A < > B {}", source)),
+            CompareMode::Difference => Some(format!("|A - B| x{} {}", self.gain, source)),
+        }
+    }
+}
+
+/// The largest region of `aspect` ratio centered in `viewport`.
+fn fit(viewport: (i32, i32, i32, i32), aspect: f32) -> (i32, i32, i32, i32) {
+    let (x, y, width, height) = viewport;
+    let fitted_width = (width as f32).min(height as f32 * aspect).round() as i32;
+    let fitted_height = (width as f32 / aspect).min(height as f32).round() as i32;
+    (
+        x + (width - fitted_width) / 2,
+        y + (height - fitted_height) / 2,
+        fitted_width,
+        fitted_height,
+    )
+}
+
+/// Reads the content of `path` at `revision` from the git repository it is in.
+fn read_revision(path: &Path, revision: &str) -> std::io::Result<String> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "expected a file path")
+    })?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("{}:./{}", revision, name.to_string_lossy()))
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "`git show` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(std::io::Error::other)
+}
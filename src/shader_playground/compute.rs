@@ -0,0 +1,474 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use log::{error, info};
+
+use crate::{
+    compat,
+    gl::{self, types::GLenum},
+    program_cache::ProgramCache,
+    shader::{Error, ShaderProgram, ShaderProgramBuilder},
+    storage_buffer::StorageBuffer,
+};
+
+use super::{uniforms::Uniforms, variants};
+
+/// Bindings 0 to 2 are taken by the diagnostic view, `debugPrintf` and the profiler.
+const FIRST_BUFFER_BINDING: u32 = 3;
+
+/// How many work groups a pass dispatches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dispatch {
+    Groups([u32; 3]),
+    /// Enough work groups to cover every pixel of the render resolution.
+    Resolution,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageSize {
+    Fixed(u32, u32),
+    /// Follows the render resolution. The image is cleared when it changes.
+    Resolution,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ImageFormat {
+    Rgba32F,
+    Rgba16F,
+    Rgba8,
+    R32F,
+}
+
+impl ImageFormat {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "rgba32f" => Some(Self::Rgba32F),
+            "rgba16f" => Some(Self::Rgba16F),
+            "rgba8" => Some(Self::Rgba8),
+            "r32f" => Some(Self::R32F),
+            _ => None,
+        }
+    }
+
+    fn internal_format(self) -> GLenum {
+        match self {
+            Self::Rgba32F => gl::RGBA32F,
+            Self::Rgba16F => gl::RGBA16F,
+            Self::Rgba8 => gl::RGBA8,
+            Self::R32F => gl::R32F,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BufferDeclaration {
+    name: String,
+    /// Size in bytes.
+    size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ImageDeclaration {
+    name: String,
+    size: ImageSize,
+    format: ImageFormat,
+}
+
+/// The `// @...` directives of a compute shader.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Declarations {
+    dispatch: Dispatch,
+    buffers: Vec<BufferDeclaration>,
+    images: Vec<ImageDeclaration>,
+}
+
+/// Parses the directives of a compute shader:
+/// - `// @dispatch <x> [<y> [<z>]]` the number of work groups, or
+///   `// @dispatch resolution` (the default) for one invocation per pixel.
+/// - `// @buffer <Block> <bytes>` a storage buffer for `buffer <Block> { ... };`.
+/// - `// @image <name> <width>x<height>|resolution [rgba32f|rgba16f|rgba8|r32f]`
+///   an image for `layout(<format>) uniform image2D <name>;`. Fragment and
+///   vertex shaders can sample it as `uniform sampler2D <name>;`.
+fn parse_declarations(source: &str) -> Result<Declarations, Error> {
+    let mut declarations = Declarations {
+        dispatch: Dispatch::Resolution,
+        buffers: Vec::new(),
+        images: Vec::new(),
+    };
+
+    for (line, text) in source.lines().enumerate() {
+        let Some(directive) = text.trim().strip_prefix("//").map(str::trim) else {
+            continue;
+        };
+        let mut words = directive.split_whitespace();
+        let err =
+            |message: &str| Error::PreprocessingError(format!("line {}: {}", line + 1, message));
+        let parse_number = |word: Option<&str>| -> Result<u32, Error> {
+            word.and_then(|word| word.parse().ok())
+                .filter(|&n| n > 0)
+                .ok_or_else(|| err("expected a positive number"))
+        };
+
+        match words.next() {
+            Some("@dispatch") => {
+                declarations.dispatch = match words.next() {
+                    Some("resolution") => Dispatch::Resolution,
+                    x => {
+                        let x = parse_number(x)?;
+                        let y = words.next().map(|y| parse_number(Some(y))).transpose()?;
+                        let z = words.next().map(|z| parse_number(Some(z))).transpose()?;
+                        Dispatch::Groups([x, y.unwrap_or(1), z.unwrap_or(1)])
+                    }
+                };
+            }
+            Some("@buffer") => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| err("`@buffer` needs a block name"))?;
+                let size = parse_number(words.next())? as usize;
+                declarations.buffers.push(BufferDeclaration {
+                    name: name.to_string(),
+                    size,
+                });
+            }
+            Some("@image") => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| err("`@image` needs a uniform name"))?;
+                let size = match words.next() {
+                    Some("resolution") => ImageSize::Resolution,
+                    Some(size) => {
+                        let (width, height) = size
+                            .split_once('x')
+                            .ok_or_else(|| err("expected `<width>x<height>` or `resolution`"))?;
+                        ImageSize::Fixed(parse_number(Some(width))?, parse_number(Some(height))?)
+                    }
+                    None => return Err(err("`@image` needs a size")),
+                };
+                let format = match words.next() {
+                    Some(format) => ImageFormat::parse(format)
+                        .ok_or_else(|| err("expected `rgba32f`, `rgba16f`, `rgba8` or `r32f`"))?,
+                    None => ImageFormat::Rgba32F,
+                };
+                declarations.images.push(ImageDeclaration {
+                    name: name.to_string(),
+                    size,
+                    format,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(declarations)
+}
+
+/// A texture compute shaders read and write through `image2D`.
+struct StorageImage {
+    declaration: ImageDeclaration,
+    texture: u32,
+    width: u32,
+    height: u32,
+}
+
+impl StorageImage {
+    fn new(declaration: ImageDeclaration, resolution: (u32, u32)) -> Self {
+        let (width, height) = match declaration.size {
+            ImageSize::Fixed(width, height) => (width, height),
+            ImageSize::Resolution => resolution,
+        };
+        let mut texture = 0;
+        unsafe {
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexStorage2D(
+                gl::TEXTURE_2D,
+                1,
+                declaration.format.internal_format(),
+                width.max(1) as _,
+                height.max(1) as _,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+
+        let this = Self {
+            declaration,
+            texture,
+            width,
+            height,
+        };
+        this.clear();
+        this
+    }
+
+    fn clear(&self) {
+        compat::clear_texture(self.texture);
+    }
+}
+
+impl Drop for StorageImage {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture);
+        }
+    }
+}
+
+struct NamedBuffer {
+    declaration: BufferDeclaration,
+    buffer: StorageBuffer,
+}
+
+struct Pass {
+    program: ShaderProgram,
+    dispatch: Dispatch,
+    local_size: [u32; 3],
+    uniforms: Uniforms,
+    frame_location: i32,
+}
+
+/// Compute shaders dispatched in order before every frame. Their storage
+/// buffers and images are shared by name between all passes and the shaders
+/// drawing the frame, and persist across frames.
+pub struct ComputePasses {
+    pub paths: Vec<PathBuf>,
+    passes: Vec<Pass>,
+    /// The binding of a buffer is its index plus `FIRST_BUFFER_BINDING`.
+    buffers: Vec<NamedBuffer>,
+    /// The image and texture unit of an image is its index.
+    images: Vec<StorageImage>,
+    /// The number of frames since the resources were last cleared, as `uFrame`.
+    frame: u32,
+    /// The `#define`s the passes were last compiled with, `None` before the
+    /// first [`Self::load`].
+    defines: Option<String>,
+}
+
+impl ComputePasses {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            paths,
+            passes: Vec::new(),
+            buffers: Vec::new(),
+            images: Vec::new(),
+            frame: 0,
+            defines: None,
+        }
+    }
+
+    pub fn defines(&self) -> Option<&str> {
+        self.defines.as_deref()
+    }
+
+    /// Whether `path` is one of the compute shaders.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths.iter().any(|p| super::same_file(p, path))
+    }
+
+    /// (Re)compiles all passes with `defines` after their `#version` line.
+    /// Resources whose declaration did not change are kept.
+    pub fn load(&mut self, resolution: (u32, u32), defines: &str, cache: &mut ProgramCache) {
+        self.defines = Some(defines.to_string());
+        match self.try_load(resolution, defines, cache) {
+            Ok(()) => info!("{} compute pass(es) successfully loaded", self.passes.len()),
+            Err(err) => {
+                error!("could not load the compute passes. Error: {:#}", err);
+                self.passes.clear();
+            }
+        }
+    }
+
+    fn try_load(
+        &mut self,
+        resolution: (u32, u32),
+        defines: &str,
+        cache: &mut ProgramCache,
+    ) -> anyhow::Result<()> {
+        let mut passes = Vec::new();
+        let mut buffers: Vec<BufferDeclaration> = Vec::new();
+        let mut images: Vec<ImageDeclaration> = Vec::new();
+        for path in &self.paths {
+            let source = std::fs::read_to_string(path)
+                .with_context(|| format!("reading `{}`", path.display()))?;
+            let declarations =
+                parse_declarations(&source).with_context(|| format!("in `{}`", path.display()))?;
+            let program = ShaderProgramBuilder::new()
+                .compute(&variants::inject_after_version(&source, defines))
+                .build_cached(cache)
+                .with_context(|| format!("compiling `{}`", path.display()))?;
+
+            for buffer in declarations.buffers {
+                match buffers.iter().find(|b| b.name == buffer.name) {
+                    Some(existing) if *existing != buffer => {
+                        bail!("buffer `{}` is declared with different sizes", buffer.name)
+                    }
+                    Some(_) => {}
+                    None => buffers.push(buffer),
+                }
+            }
+            for image in declarations.images {
+                match images.iter().find(|i| i.name == image.name) {
+                    Some(existing) if *existing != image => {
+                        bail!("image `{}` is declared differently", image.name)
+                    }
+                    Some(_) => {}
+                    None => images.push(image),
+                }
+            }
+
+            passes.push(Pass {
+                local_size: program.compute_work_group_size(),
+                uniforms: Uniforms::init(&program),
+                frame_location: program.uniform_location("uFrame"),
+                dispatch: declarations.dispatch,
+                program,
+            });
+        }
+
+        // keep the contents of unchanged resources
+        let mut old_buffers = std::mem::take(&mut self.buffers);
+        for declaration in buffers {
+            let position = old_buffers
+                .iter()
+                .position(|b| b.declaration == declaration);
+            let buffer = match position {
+                Some(position) => old_buffers.swap_remove(position),
+                None => NamedBuffer {
+                    buffer: StorageBuffer::new(declaration.size.div_ceil(4)),
+                    declaration,
+                },
+            };
+            self.buffers.push(buffer);
+        }
+        let mut old_images = std::mem::take(&mut self.images);
+        for declaration in images {
+            let position = old_images.iter().position(|i| i.declaration == declaration);
+            let image = match position {
+                Some(position) => old_images.swap_remove(position),
+                None => StorageImage::new(declaration, resolution),
+            };
+            self.images.push(image);
+        }
+
+        for pass in &passes {
+            self.bind_to(&pass.program);
+        }
+        self.passes = passes;
+        Ok(())
+    }
+
+    /// Points the storage blocks, images and samplers of `program` named like
+    /// a buffer or image at it. The bindings are kept by the program, so this is
+    /// needed once after compiling it.
+    pub fn bind_to(&self, program: &ShaderProgram) {
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            program.storage_block_binding(
+                &buffer.declaration.name,
+                FIRST_BUFFER_BINDING + index as u32,
+            );
+        }
+        program.use_program();
+        for (unit, image) in self.images.iter().enumerate() {
+            program.uniform_unit(&image.declaration.name, unit as _);
+        }
+    }
+
+    /// Binds the buffers and the images as textures for drawing.
+    pub fn bind_resources(&self) {
+        self.bind_buffers();
+        unsafe {
+            for (unit, image) in self.images.iter().enumerate() {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                gl::BindTexture(gl::TEXTURE_2D, image.texture);
+            }
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+
+    /// Unbinds the textures bound by [`Self::bind_resources`].
+    pub fn unbind_resources(&self) {
+        unsafe {
+            for unit in 0..self.images.len() {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+            }
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+    }
+
+    fn bind_buffers(&self) {
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            buffer.buffer.bind(FIRST_BUFFER_BINDING + index as u32);
+        }
+    }
+
+    /// Dispatches every pass once, with the same inputs as the frame.
+    pub fn run(&mut self, resolution: (u32, u32), mouse_position: (f32, f32), time: f32) {
+        // images following the render resolution start over when it changes
+        for image in &mut self.images {
+            if image.declaration.size == ImageSize::Resolution
+                && (image.width, image.height) != resolution
+            {
+                *image = StorageImage::new(image.declaration.clone(), resolution);
+            }
+        }
+
+        self.bind_buffers();
+        unsafe {
+            for (unit, image) in self.images.iter().enumerate() {
+                gl::BindImageTexture(
+                    unit as _,
+                    image.texture,
+                    0,
+                    gl::FALSE,
+                    0,
+                    gl::READ_WRITE,
+                    image.declaration.format.internal_format(),
+                );
+            }
+
+            for pass in &self.passes {
+                pass.program.use_program();
+                pass.uniforms.bind(
+                    (resolution.0 as f32, resolution.1 as f32),
+                    mouse_position,
+                    time,
+                );
+                gl::Uniform1i(pass.frame_location, self.frame as _);
+
+                let groups = match pass.dispatch {
+                    Dispatch::Groups(groups) => groups,
+                    Dispatch::Resolution => [
+                        resolution.0.div_ceil(pass.local_size[0]),
+                        resolution.1.div_ceil(pass.local_size[1]),
+                        1,
+                    ],
+                };
+                gl::DispatchCompute(groups[0], groups[1], groups[2]);
+
+                // make the writes visible to the next pass and the draw
+                gl::MemoryBarrier(
+                    gl::SHADER_STORAGE_BARRIER_BIT
+                        | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT
+                        | gl::TEXTURE_FETCH_BARRIER_BIT,
+                );
+            }
+        }
+        self.frame += 1;
+    }
+
+    /// Clears all buffers and images and restarts `uFrame` at zero.
+    pub fn reset(&mut self) {
+        for buffer in &self.buffers {
+            buffer.buffer.clear();
+        }
+        for image in &self.images {
+            image.clear();
+        }
+        self.frame = 0;
+        info!("compute state reset");
+    }
+}
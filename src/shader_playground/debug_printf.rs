@@ -0,0 +1,306 @@
+use std::fmt::Write;
+
+use log::info;
+
+use crate::{shader::ShaderProgram, storage_buffer::StorageBuffer};
+
+use super::preprocessor::{
+    DEBUG_PRINTF_BLOCK, DEBUG_PRINTF_CAPACITY, TAG_BOOL, TAG_COMPONENTS_SHIFT, TAG_FLOAT, TAG_INT,
+    TAG_UINT,
+};
+
+/// The storage buffer binding `debugPrintf` writes to.
+const BINDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    Float(f32),
+    Int(i32),
+    Uint(u32),
+    Bool(bool),
+}
+
+impl Scalar {
+    fn decode(tag: u32, word: u32) -> Option<Self> {
+        match tag {
+            TAG_FLOAT => Some(Self::Float(f32::from_bits(word))),
+            TAG_INT => Some(Self::Int(word as i32)),
+            TAG_UINT => Some(Self::Uint(word)),
+            TAG_BOOL => Some(Self::Bool(word != 0)),
+            _ => None,
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Self::Float(v) => v as f64,
+            Self::Int(v) => v as f64,
+            Self::Uint(v) => v as f64,
+            Self::Bool(v) => v as u8 as f64,
+        }
+    }
+
+    fn as_i64(self) -> i64 {
+        match self {
+            Self::Float(v) => v as i64,
+            Self::Int(v) => v as i64,
+            Self::Uint(v) => v as i64,
+            Self::Bool(v) => v as i64,
+        }
+    }
+
+    /// Formats the value according to a printf conversion (`f`, `e`, `g`, `d`, `i`, `u`, `x`).
+    fn format(self, conversion: char, precision: Option<usize>, out: &mut String) {
+        let _ = match conversion {
+            'f' => write!(out, "{:.*}", precision.unwrap_or(6), self.as_f64()),
+            'e' => write!(out, "{:.*e}", precision.unwrap_or(6), self.as_f64()),
+            'd' | 'i' | 'u' => write!(out, "{}", self.as_i64()),
+            'x' => write!(out, "{:x}", self.as_i64()),
+            _ => match self {
+                Self::Float(v) => match precision {
+                    Some(precision) => write!(out, "{:.*}", precision, v),
+                    None => write!(out, "{}", v),
+                },
+                Self::Int(v) => write!(out, "{}", v),
+                Self::Uint(v) => write!(out, "{}", v),
+                Self::Bool(v) => write!(out, "{}", v),
+            },
+        };
+    }
+}
+
+/// Collects the output of the `debugPrintf` calls of a shader.
+pub struct DebugPrintf {
+    formats: Vec<String>,
+    buffer: StorageBuffer,
+    pixel_location: i32,
+    last_output: Vec<String>,
+}
+
+impl DebugPrintf {
+    /// Returns `None` if the shader does not call `debugPrintf`.
+    pub fn new(shader: &ShaderProgram, formats: Vec<String>) -> Option<Self> {
+        if formats.is_empty() || !shader.storage_block_binding(DEBUG_PRINTF_BLOCK, BINDING) {
+            return None;
+        }
+        Some(Self {
+            formats,
+            buffer: StorageBuffer::new(DEBUG_PRINTF_CAPACITY),
+            pixel_location: shader.uniform_location("uDebugPixel"),
+            last_output: Vec::new(),
+        })
+    }
+
+    /// Keeps the `debugPrintf` calls of a shader without a [`DebugPrintf`] from
+    /// writing anywhere. Expects the shader to be in use.
+    pub fn disable(shader: &ShaderProgram) {
+        unsafe {
+            crate::gl::Uniform2i(shader.uniform_location("uDebugPixel"), -1, -1);
+        }
+    }
+
+    /// Clears the previous output and selects the pixel (measured from the
+    /// bottom left) whose calls are recorded. Expects the shader to be in use.
+    pub fn bind(&self, pixel: Option<(u32, u32)>) {
+        let pixel = pixel.map(|(x, y)| (x as i32, y as i32)).unwrap_or((-1, -1));
+        self.buffer.clear();
+        self.buffer.bind(BINDING);
+        unsafe {
+            crate::gl::Uniform2i(self.pixel_location, pixel.0, pixel.1);
+        }
+    }
+
+    /// Reads back and formats the output of the last draw. New output is
+    /// also printed to the terminal.
+    pub fn read(&mut self) -> &[String] {
+        let data = self.buffer.read();
+        let len = (data[0] as usize).min(data.len() - 1);
+        let output = decode(&self.formats, &data[1..=len]);
+
+        if output != self.last_output {
+            for line in &output {
+                info!("debugPrintf: {}", line);
+            }
+            self.last_output = output;
+        }
+
+        &self.last_output
+    }
+}
+
+/// Formats the calls recorded in `words`. A call cut off by the end of the
+/// buffer is printed with the arguments that fit.
+fn decode(formats: &[String], mut words: &[u32]) -> Vec<String> {
+    let mut lines = Vec::new();
+    while let [id, argc, rest @ ..] = words {
+        words = rest;
+
+        // every argument takes at least its tag word, and a corrupt `argc` must
+        // not reserve more than the buffer can hold
+        let mut args = Vec::with_capacity((*argc as usize).min(words.len()));
+        for _ in 0..*argc {
+            let Some((&tag, rest)) = words.split_first() else {
+                break;
+            };
+            let components = (tag >> TAG_COMPONENTS_SHIFT) as usize;
+            let tag = tag & ((1 << TAG_COMPONENTS_SHIFT) - 1);
+            let Some(values) = rest.get(..components) else {
+                // the rest of the buffer is a partial argument, not a call
+                words = &[];
+                break;
+            };
+            words = &rest[components..];
+            args.push(
+                values
+                    .iter()
+                    .filter_map(|&word| Scalar::decode(tag, word))
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        let format = formats
+            .get(*id as usize)
+            .map(String::as_str)
+            .unwrap_or("<unknown debugPrintf call>");
+        lines.push(format_printf(format, &args));
+    }
+    lines
+}
+
+/// A minimal printf: `%%`, and `%[.precision]<conversion>` where vectors are
+/// printed as `(x, y, ...)` with the same conversion for each component.
+fn format_printf(format: &str, args: &[Vec<Scalar>]) -> String {
+    let mut out = String::with_capacity(format.len());
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            out.push('%');
+            continue;
+        }
+
+        let mut precision = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut digits = String::new();
+            while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(*digit);
+                chars.next();
+            }
+            precision = digits.parse().ok();
+        }
+        let conversion = chars.next().unwrap_or('v');
+
+        match args.next() {
+            Some(values) if values.len() == 1 => values[0].format(conversion, precision, &mut out),
+            Some(values) => {
+                out.push('(');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    value.format(conversion, precision, &mut out);
+                }
+                out.push(')');
+            }
+            None => out.push_str("<missing>"),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn float(v: f32) -> Vec<Scalar> {
+        vec![Scalar::Float(v)]
+    }
+
+    /// The words `_dbgWrite` pushes for one argument.
+    fn arg(tag: u32, words: &[u32]) -> Vec<u32> {
+        let mut out = vec![tag | ((words.len() as u32) << TAG_COMPONENTS_SHIFT)];
+        out.extend_from_slice(words);
+        out
+    }
+
+    #[test]
+    fn formats_conversions() {
+        assert_eq!(format_printf("x = %.2f", &[float(1.23456)]), "x = 1.23");
+        assert_eq!(format_printf("%f", &[float(0.5)]), "0.500000");
+        assert_eq!(format_printf("%.1e", &[float(1234.5)]), "1.2e3");
+        assert_eq!(format_printf("%d", &[float(-2.7)]), "-2");
+        assert_eq!(format_printf("%x", &[vec![Scalar::Uint(255)]]), "ff");
+        assert_eq!(format_printf("%v", &[vec![Scalar::Bool(true)]]), "true");
+        assert_eq!(format_printf("%v", &[float(0.25)]), "0.25");
+    }
+
+    #[test]
+    fn formats_vectors_component_wise() {
+        let v = vec![Scalar::Int(1), Scalar::Int(-2), Scalar::Int(3)];
+        assert_eq!(format_printf("p = %d", &[v]), "p = (1, -2, 3)");
+    }
+
+    #[test]
+    fn escapes_percent() {
+        assert_eq!(
+            format_printf("100%% of %d", &[vec![Scalar::Int(3)]]),
+            "100% of 3"
+        );
+        assert_eq!(format_printf("%%d", &[]), "%d");
+    }
+
+    #[test]
+    fn marks_missing_and_ignores_extra_arguments() {
+        assert_eq!(
+            format_printf("%d and %d", &[vec![Scalar::Int(1)]]),
+            "1 and <missing>"
+        );
+        assert_eq!(format_printf("%d", &[float(1.0), float(2.0)]), "1");
+        assert_eq!(format_printf("no args", &[float(1.0)]), "no args");
+    }
+
+    #[test]
+    fn decodes_calls() {
+        let formats = vec!["a = %d".to_string(), "v = %.1f".to_string()];
+        let mut words = vec![0, 1];
+        words.extend(arg(TAG_INT, &[(-5i32) as u32]));
+        words.extend([1, 1]);
+        words.extend(arg(TAG_FLOAT, &[0.5f32.to_bits(), 2.0f32.to_bits()]));
+        assert_eq!(decode(&formats, &words), ["a = -5", "v = (0.5, 2.0)"]);
+    }
+
+    #[test]
+    fn decodes_unknown_calls() {
+        let mut words = vec![7, 1];
+        words.extend(arg(TAG_UINT, &[3]));
+        assert_eq!(decode(&[], &words), ["<unknown debugPrintf call>"]);
+    }
+
+    #[test]
+    fn decodes_truncated_buffers() {
+        let formats = vec!["%d %d".to_string()];
+
+        // the second argument is cut off in the middle of its components
+        let mut words = vec![0, 2];
+        words.extend(arg(TAG_INT, &[1]));
+        words.extend(&arg(TAG_INT, &[2, 3, 4])[..2]);
+        assert_eq!(decode(&formats, &words), ["1 <missing>"]);
+
+        // only the id of a call fits
+        assert_eq!(decode(&formats, &[0]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn decodes_corrupt_argument_counts() {
+        let formats = vec!["%d %d".to_string()];
+        let mut words = vec![0, u32::MAX];
+        words.extend(arg(TAG_INT, &[1]));
+        assert_eq!(decode(&formats, &words), ["1 <missing>"]);
+    }
+}
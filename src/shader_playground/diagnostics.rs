@@ -0,0 +1,130 @@
+use log::debug;
+
+use crate::{
+    gl,
+    overlay::{Anchor, TextOverlay},
+    plane_buffer::PlaneBuffer,
+    render_target::{Filter, Format, RenderTarget},
+    shader::ShaderProgram,
+    storage_buffer::StorageBuffer,
+};
+
+const VERTEX_SHADER: &str = include_str!("../res/basic_shaders/vert.glsl");
+const FRAG_SHADER_DIAGNOSTICS: &str = include_str!("../res/basic_shaders/frag_diagnostics.glsl");
+
+const MARGIN: (i32, i32) = (8, 8);
+
+/// The number of pixels in each diagnostic category.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub nan: u32,
+    pub inf: u32,
+    pub negative: u32,
+    pub over_one: u32,
+}
+
+impl Counts {
+    pub fn lines(&self, total: u32) -> Vec<String> {
+        let percent = |count: u32| 100.0 * count as f32 / total.max(1) as f32;
+        vec![
+            format!("NaN (magenta) {:>9} {:>7.3}%", self.nan, percent(self.nan)),
+            format!("Inf (cyan)    {:>9} {:>7.3}%", self.inf, percent(self.inf)),
+            format!(
+                "< 0 (blue)    {:>9} {:>7.3}%",
+                self.negative,
+                percent(self.negative)
+            ),
+            format!(
+                "> 1 (orange)  {:>9} {:>7.3}%",
+                self.over_one,
+                percent(self.over_one)
+            ),
+        ]
+    }
+}
+
+/// Highlights NaN, infinite, negative and larger than one values of a float render target.
+pub struct Diagnostics {
+    program: ShaderProgram,
+    texture_location: i32,
+    counts_buffer: StorageBuffer,
+    target: Option<RenderTarget>,
+    last_counts: Counts,
+}
+
+impl Diagnostics {
+    pub fn new() -> anyhow::Result<Self> {
+        let program = ShaderProgram::new(VERTEX_SHADER, FRAG_SHADER_DIAGNOSTICS)?;
+        let texture_location = program.uniform_location("uTexture");
+        Ok(Self {
+            program,
+            texture_location,
+            counts_buffer: StorageBuffer::new(4),
+            target: None,
+            last_counts: Counts::default(),
+        })
+    }
+
+    /// Classifies every pixel of `source` and returns the target holding the
+    /// highlighted image, ready to be presented.
+    pub fn run(
+        &mut self,
+        source: &RenderTarget,
+        plane: &PlaneBuffer,
+        filter: Filter,
+    ) -> anyhow::Result<&RenderTarget> {
+        let target = match self.target.take() {
+            Some(mut target) => {
+                target.resize(source.width, source.height)?;
+                target
+            }
+            None => RenderTarget::new(source.width, source.height, Format::Rgba8, filter)?,
+        };
+        let target = &*self.target.insert(target);
+
+        self.counts_buffer.clear();
+        self.counts_buffer.bind(0);
+
+        target.bind();
+        self.program.use_program();
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, source.texture);
+            gl::Uniform1i(self.texture_location, 0);
+
+            gl::BindVertexArray(plane.vao);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+        RenderTarget::unbind();
+
+        let counts = self.counts_buffer.read();
+        let counts = Counts {
+            nan: counts[0],
+            inf: counts[1],
+            negative: counts[2],
+            over_one: counts[3],
+        };
+        if counts != self.last_counts {
+            debug!(
+                "NaN: {}, Inf: {}, negative: {}, > 1: {}",
+                counts.nan, counts.inf, counts.negative, counts.over_one
+            );
+            self.last_counts = counts;
+        }
+
+        Ok(target)
+    }
+
+    /// Draws the counts of the last run.
+    pub fn draw_counts(&self, plane: &PlaneBuffer, overlay: &TextOverlay, window_size: (u32, u32)) {
+        let total = self
+            .target
+            .as_ref()
+            .map(|target| target.width * target.height)
+            .unwrap_or(0);
+        let lines = self.last_counts.lines(total);
+        overlay.draw(plane, window_size, Anchor::TopRight, MARGIN, &lines);
+    }
+}
@@ -0,0 +1,171 @@
+use log::info;
+
+use crate::{
+    gl,
+    overlay::{Anchor, TextOverlay},
+    plane_buffer::PlaneBuffer,
+    render_target::{Presenter, RenderTarget},
+};
+
+/// Number of pixels shown along each side of the loupe.
+const LOUPE_PIXELS: i32 = 15;
+/// Size in window pixels of a single magnified pixel.
+const LOUPE_ZOOM: i32 = 12;
+const MARGIN: (i32, i32) = (8, 8);
+
+/// The value of a single rendered pixel.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelInfo {
+    /// Measured from the bottom left of the render target.
+    pub pixel: (u32, u32),
+    pub resolution: (u32, u32),
+    pub value: [f32; 4],
+}
+
+impl PixelInfo {
+    pub fn frag_coord(&self) -> (f32, f32) {
+        (self.pixel.0 as f32 + 0.5, self.pixel.1 as f32 + 0.5)
+    }
+
+    pub fn uv(&self) -> (f32, f32) {
+        let (x, y) = self.frag_coord();
+        (x / self.resolution.0 as f32, y / self.resolution.1 as f32)
+    }
+
+    pub fn lines(&self, pinned: bool) -> Vec<String> {
+        let (x, y) = self.frag_coord();
+        let (u, v) = self.uv();
+        let [r, g, b, a] = self.value;
+        vec![
+            format!(
+                "pixel        ({}, {}){}",
+                self.pixel.0,
+                self.pixel.1,
+                if pinned { " [pinned]" } else { "" }
+            ),
+            format!("gl_FragCoord ({:.1}, {:.1})", x, y),
+            format!("iUv          ({:.6}, {:.6})", u, v),
+            format!("r            {:.6}", r),
+            format!("g            {:.6}", g),
+            format!("b            {:.6}", b),
+            format!("a            {:.6}", a),
+        ]
+    }
+}
+
+/// Reads back exact pixel values of the rendered image.
+#[derive(Debug, Default)]
+pub struct Inspector {
+    pub enabled: bool,
+    pub loupe: bool,
+    pinned: Option<(u32, u32)>,
+    log_pinned: bool,
+}
+
+impl Inspector {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        info!(
+            "pixel inspector {}",
+            if self.enabled { "enabled" } else { "disabled" }
+        );
+    }
+
+    pub fn toggle_loupe(&mut self) {
+        self.loupe = !self.loupe;
+    }
+
+    pub fn pinned(&self) -> Option<(u32, u32)> {
+        self.pinned
+    }
+
+    pub fn pin(&mut self, pixel: Option<(u32, u32)>) {
+        self.pinned = pixel;
+        self.log_pinned = pixel.is_some();
+    }
+
+    /// Reads the pinned pixel, or `hovered` if nothing is pinned.
+    pub fn inspect(
+        &mut self,
+        target: &RenderTarget,
+        hovered: Option<(u32, u32)>,
+    ) -> Option<PixelInfo> {
+        let pixel = self.pinned.or(hovered)?;
+        if pixel.0 >= target.width || pixel.1 >= target.height {
+            return None;
+        }
+
+        let info = PixelInfo {
+            pixel,
+            resolution: (target.width, target.height),
+            value: target.read_pixel(pixel.0, pixel.1),
+        };
+
+        if self.log_pinned {
+            self.log_pinned = false;
+            for line in info.lines(true) {
+                info!("{}", line);
+            }
+        }
+
+        Some(info)
+    }
+
+    /// Draws a hint on how to use the inspector when no pixel is inspected.
+    pub fn draw_hint(&self, plane: &PlaneBuffer, overlay: &TextOverlay, window_size: (u32, u32)) {
+        let lines =
+            ["hover or left click a pixel to inspect it (right click to unpin, L for the loupe)"];
+        overlay.draw(plane, window_size, Anchor::TopLeft, MARGIN, &lines);
+    }
+
+    /// Draws the loupe and the values of `info` in the corners of the window.
+    pub fn draw(
+        &self,
+        info: &PixelInfo,
+        target: &RenderTarget,
+        plane: &PlaneBuffer,
+        presenter: &Presenter,
+        overlay: &TextOverlay,
+        window_size: (u32, u32),
+    ) {
+        let lines = info.lines(self.pinned.is_some());
+        overlay.draw(plane, window_size, Anchor::TopLeft, MARGIN, &lines);
+
+        if !self.loupe {
+            return;
+        }
+
+        let size = LOUPE_PIXELS * LOUPE_ZOOM;
+        let x = window_size.0 as i32 - size - MARGIN.0;
+        let y = MARGIN.1;
+        let half = (LOUPE_PIXELS / 2) as f32;
+        let uv_rect = (
+            (info.pixel.0 as f32 - half) / target.width as f32,
+            (info.pixel.1 as f32 - half) / target.height as f32,
+            LOUPE_PIXELS as f32 / target.width as f32,
+            LOUPE_PIXELS as f32 / target.height as f32,
+        );
+        presenter.present_region(target, plane, (x, y, size, size), uv_rect, true);
+
+        // outline the inspected pixel
+        let center = (
+            x + LOUPE_PIXELS / 2 * LOUPE_ZOOM,
+            y + LOUPE_PIXELS / 2 * LOUPE_ZOOM,
+        );
+        let edges = [
+            (center.0, center.1, LOUPE_ZOOM, 1),
+            (center.0, center.1 + LOUPE_ZOOM - 1, LOUPE_ZOOM, 1),
+            (center.0, center.1, 1, LOUPE_ZOOM),
+            (center.0 + LOUPE_ZOOM - 1, center.1, 1, LOUPE_ZOOM),
+        ];
+        unsafe {
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::ClearColor(1.0, 1.0, 0.0, 1.0);
+            for (x, y, width, height) in edges {
+                gl::Scissor(x, y, width, height);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            gl::Disable(gl::SCISSOR_TEST);
+        }
+    }
+}
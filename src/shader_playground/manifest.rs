@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+use crate::shader::Error;
+
+use super::variants::is_identifier;
+
+/// The manifest of the shader at `shader`, next to it with the extension
+/// `.toml`. Only its `[defines]` table is read, e.g.
+///
+/// ```toml
+/// [defines]
+/// STEPS = 64
+/// QUALITY = "QUALITY_HIGH"
+/// SHADOWS = true
+/// ```
+pub fn path(shader: &Path) -> PathBuf {
+    shader.with_extension("toml")
+}
+
+/// The `[defines]` of the manifest of `shader`, none if it has no manifest.
+pub fn load_defines(shader: &Path) -> Result<Vec<(String, String)>, Error> {
+    let path = path(shader);
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(Error::PreprocessingError(format!(
+                "could not read `{}`: {}",
+                path.display(),
+                err
+            )))
+        }
+    };
+    parse_defines(&source)
+        .map_err(|err| Error::PreprocessingError(format!("`{}` {}", path.display(), err)))
+}
+
+/// `options` followed by the `defines` they do not override.
+pub fn merge(options: &[(String, String)], defines: &[(String, String)]) -> Vec<(String, String)> {
+    let overridden = |name: &str| options.iter().any(|(option, _)| option == name);
+    options
+        .iter()
+        .chain(defines.iter().filter(|(name, _)| !overridden(name)))
+        .cloned()
+        .collect()
+}
+
+/// Reads the `NAME = VALUE` pairs of the `[defines]` table. Strings are
+/// defined without their quotes, booleans as `1` or `0`, and anything else
+/// as it is written. Other tables are skipped.
+fn parse_defines(source: &str) -> Result<Vec<(String, String)>, String> {
+    let mut defines = Vec::<(String, String)>::new();
+    let mut in_defines = false;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(table) = line.strip_prefix('[') {
+            let table = table.split('#').next().unwrap_or_default().trim_end();
+            in_defines = table.strip_suffix(']').map(str::trim) == Some("defines");
+            continue;
+        }
+        if !in_defines {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", index + 1, message);
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `NAME = VALUE`"))?;
+        let name = name.trim();
+        if !is_identifier(name) {
+            return Err(error(&format!("invalid macro name `{}`", name)));
+        }
+        if defines.iter().any(|(other, _)| other == name) {
+            return Err(error(&format!("`{}` is defined twice", name)));
+        }
+        let value = parse_value(value.trim()).ok_or_else(|| error("invalid value"))?;
+        defines.push((name.to_string(), value));
+    }
+    Ok(defines)
+}
+
+/// A value followed by an optional comment.
+fn parse_value(value: &str) -> Option<String> {
+    let (value, rest) = if let Some(literal) = value.strip_prefix('\'') {
+        let (literal, rest) = literal.split_once('\'')?;
+        (literal.to_string(), rest)
+    } else if let Some(string) = value.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = string.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (end, '"') => break end,
+                (_, '\\') => out.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    c @ ('"' | '\\') => c,
+                    _ => return None,
+                }),
+                (_, c) => out.push(c),
+            }
+        };
+        (out, &string[end + 1..])
+    } else {
+        let value = match value.split('#').next().unwrap_or_default().trim() {
+            "" => return None,
+            "true" => "1",
+            "false" => "0",
+            value => value,
+        };
+        (value.to_string(), "")
+    };
+    let rest = rest.trim();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(defines: &[(&str, &str)]) -> Vec<(String, String)> {
+        defines
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_the_defines_table() {
+        let source = "\
+title = \"plasma\"
+
+[defines]
+# the number of raymarching steps
+STEPS = 64
+QUALITY = \"QUALITY_HIGH\" # the highest tier
+SHADOWS = true
+FOG = false
+TINT = 'vec3(1.0, 0.5, 0.2)'
+
+[other]
+STEPS = 8
+";
+        assert_eq!(
+            parse_defines(source).unwrap(),
+            pairs(&[
+                ("STEPS", "64"),
+                ("QUALITY", "QUALITY_HIGH"),
+                ("SHADOWS", "1"),
+                ("FOG", "0"),
+                ("TINT", "vec3(1.0, 0.5, 0.2)"),
+            ])
+        );
+    }
+
+    #[test]
+    fn unescapes_strings() {
+        assert_eq!(
+            parse_defines("[defines]\nNAME = \"a \\\"b\\\" \\\\ # c\"\n").unwrap(),
+            pairs(&[("NAME", "a \"b\" \\ # c")])
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        for (source, message) in [
+            ("[defines]\nSTEPS\n", "line 2: expected `NAME = VALUE`"),
+            ("[defines]\n2X = 1\n", "line 2: invalid macro name `2X`"),
+            ("[defines]\nA = 1\nA = 2\n", "line 3: `A` is defined twice"),
+            ("[defines]\nA = \"open\n", "line 2: invalid value"),
+            ("[defines]\nA = \"a\" b\n", "line 2: invalid value"),
+            ("[defines]\nA =\n", "line 2: invalid value"),
+        ] {
+            assert_eq!(parse_defines(source).unwrap_err(), message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn options_override_the_manifest() {
+        let options = pairs(&[("STEPS", "128")]);
+        let defines = pairs(&[("STEPS", "64"), ("SHADOWS", "1")]);
+        assert_eq!(
+            merge(&options, &defines),
+            pairs(&[("STEPS", "128"), ("SHADOWS", "1")])
+        );
+    }
+
+    #[test]
+    fn a_missing_manifest_has_no_defines() {
+        let shader = std::env::temp_dir().join("shader-playground-no-manifest.glsl");
+        assert_eq!(load_defines(&shader).unwrap(), Vec::new());
+    }
+}
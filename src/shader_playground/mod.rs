@@ -1,76 +1,185 @@
+pub mod compare;
+pub mod compute;
+pub mod debug_printf;
+pub mod diagnostics;
 pub mod file_watcher;
+pub mod inspector;
+pub mod manifest;
+pub mod normalize;
+pub mod offscreen;
+pub mod preprocessor;
+pub mod profiler;
 pub mod reloaded_shader;
+pub mod scene;
+pub mod screenshot;
+pub mod uniforms;
+pub mod validation;
+pub mod variants;
 
 use std::path::{Path, PathBuf};
 
 use anyhow::Context;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use winit::event::{ElementState, MouseButton};
 
-use crate::{game_window::app::App, gl, plane_buffer::PlaneBuffer, shader::ShaderProgram};
+use crate::{
+    game_window::app::{App, KeyInput},
+    gl,
+    image::Image,
+    overlay::{Anchor, TextOverlay},
+    plane_buffer::PlaneBuffer,
+    program_cache::ProgramCache,
+    render_target::{self, Filter, Format, Presenter, RenderTarget},
+    renderer::{self, webgpu::WgpuRenderer, Renderer},
+    shader::{self, ParallelCompile, PendingProgram, Stage},
+};
 
-use self::{file_watcher::FileWatcher, reloaded_shader::ReloadedShader};
+use self::{
+    compare::{Compare, CompareMode, CompareSource},
+    compute::ComputePasses,
+    debug_printf::DebugPrintf,
+    diagnostics::Diagnostics,
+    file_watcher::FileWatcher,
+    inspector::Inspector,
+    normalize::Dialect,
+    offscreen::FrameInputs,
+    profiler::Profiler,
+    reloaded_shader::ReloadedShader,
+    scene::{Cull, Procedural, Scene, VERTEX_SHADER_MESH},
+    screenshot::{SceneMetadata, ScreenshotMetadata, StageMetadata},
+    uniforms::Uniforms,
+    variants::Variants,
+};
 
-#[derive(Debug, Clone, Copy)]
-struct Uniforms {
-    window_resolution: i32,
-    mouse_position: i32,
-    time: i32,
-}
-
-impl Default for Uniforms {
-    fn default() -> Self {
-        Self {
-            window_resolution: -1,
-            mouse_position: -1,
-            time: -1,
-        }
-    }
-}
-
-impl Uniforms {
-    fn init(shader: &ShaderProgram) -> Self {
-        Self {
-            window_resolution: shader.uniform_location("uResolution"),
-            mouse_position: shader.uniform_location("uMouse"),
-            time: shader.uniform_location("uTime"),
-        }
-    }
-
-    fn bind(self, window_resolution: (f32, f32), mouse_position: (f32, f32), time: f32) {
-        unsafe {
-            gl::Uniform2f(
-                self.window_resolution,
-                window_resolution.0,
-                window_resolution.1,
-            );
-
-            gl::Uniform2f(
-                self.mouse_position,
-                mouse_position.0,
-                window_resolution.1 - mouse_position.1,
-            );
+const OVERLAY_MARGIN: (i32, i32) = (8, 8);
 
-            gl::Uniform1f(self.time, time);
-        }
-    }
+/// A new version of the shader and what the preprocessor found in it.
+struct PendingShader {
+    program: PendingProgram,
+    source_hash: Option<String>,
+    printf_formats: Vec<String>,
+    uses_profile: bool,
 }
 
 #[derive(Debug)]
 pub struct ShaderPlaygroundArgs {
     pub file: Option<PathBuf>,
     pub debouncer_ms: u32,
+    /// The shader is rendered at `render_scale` times the window resolution.
+    pub render_scale: f32,
+    pub scale_filter: Filter,
+    /// Render at a fixed resolution, independent of the window size. The
+    /// result is letterboxed into the window. Takes precedence over `render_scale`.
+    pub fixed_resolution: Option<(u32, u32)>,
+    /// A second shader to compare the main one against.
+    pub compare: Option<CompareSource>,
+    /// Shaders linked with the fragment shader. A vertex shader replaces the built-in one.
+    pub stages: Vec<(Stage, PathBuf)>,
+    /// An OBJ or glTF file the shader is drawn onto instead of the full screen quad.
+    pub mesh: Option<PathBuf>,
+    /// Draw vertices computed by the vertex shader instead of the full screen quad.
+    pub procedural: Option<Procedural>,
+    pub depth_test: bool,
+    pub cull: Cull,
+    /// Compute shaders dispatched in order before every frame.
+    pub compute: Vec<PathBuf>,
+    /// Reuse linked programs across reloads and runs.
+    pub program_cache: bool,
+    /// Macros defined for the shader and its stages, from `-D NAME=VALUE`.
+    pub defines: Vec<(String, String)>,
+    /// The GLSL version shaders are translated to. `None` keeps the version
+    /// of every shader.
+    pub glsl: Option<Dialect>,
+    /// Compile shaders on the driver's threads if it supports it. Otherwise a
+    /// shader is swapped in on the frame it was loaded, as replays require.
+    pub background_compile: bool,
+}
+
+/// The defaults of the command line.
+impl Default for ShaderPlaygroundArgs {
+    fn default() -> Self {
+        Self {
+            file: None,
+            debouncer_ms: 250,
+            render_scale: 1.0,
+            scale_filter: Filter::Linear,
+            fixed_resolution: None,
+            compare: None,
+            stages: Vec::new(),
+            mesh: None,
+            procedural: None,
+            depth_test: true,
+            cull: Cull::None,
+            compute: Vec::new(),
+            program_cache: true,
+            defines: Vec::new(),
+            glsl: None,
+            background_compile: true,
+        }
+    }
 }
 
 pub struct ShaderPlayground {
     quit: bool,
     shader: ReloadedShader,
+    shader_path: Option<PathBuf>,
+    source_hash: Option<String>,
     plane: PlaneBuffer,
     uniforms: Uniforms,
+    scene: Scene,
+    stage_paths: Vec<(Stage, PathBuf)>,
+    /// The stages the current shader was linked with besides the fragment
+    /// shader. Without a vertex shader the built-in one is used.
+    stages: Vec<(Stage, String)>,
+    /// The user's stages as read from disk, for the screenshot metadata.
+    stage_metadata: Vec<StageMetadata>,
+    /// Whether the mouse is orbiting the camera.
+    orbiting: bool,
+
+    render_scale: f32,
+    scale_filter: Filter,
+    fixed_resolution: Option<(u32, u32)>,
+    render_target: Option<RenderTarget>,
+    presenter: Presenter,
+    overlay: TextOverlay,
+
+    inspector: Inspector,
+    /// `Some` while the NaN/Inf diagnostic view is shown.
+    diagnostics: Option<Diagnostics>,
+    /// `Some` if the current shader calls `debugPrintf`.
+    debug_printf: Option<DebugPrintf>,
+    /// `Some` while the `PROFILE_COUNT()` heatmap is shown.
+    profiler: Option<Profiler>,
+    /// Whether the current shader calls `PROFILE_COUNT()`.
+    uses_profile: bool,
+    /// `Some` while a new version of the shader is compiling.
+    pending: Option<PendingShader>,
+    defines: Vec<(String, String)>,
+    /// The `[defines]` of the shader's manifest, see [`manifest::path`].
+    manifest: Vec<(String, String)>,
+    glsl: Option<Dialect>,
+    /// The `// @variant`s of the shader, switched with Tab and V.
+    variants: Variants,
+    /// `Some` if a second shader was given to compare against.
+    compare: Option<Compare>,
+    /// `Some` if compute shaders were given.
+    compute: Option<ComputePasses>,
+    program_cache: ProgramCache,
+    /// Renders WGSL shaders, created when the first one is loaded.
+    wgpu: Option<Box<dyn Renderer>>,
+    /// Whether the current shader is a WGSL shader. Its frames are rendered
+    /// by `wgpu` and uploaded into the render target.
+    wgsl: bool,
 
     watcher: FileWatcher,
 
-    time_root: std::time::Instant,
+    screenshot_requested: bool,
+
+    started: std::time::Instant,
+    /// The time set with [`App::set_time`], which replaces the wall clock.
+    clock: Option<f32>,
+    /// The clock when the current shader was loaded, where `uTime` starts.
+    time_root: f32,
 
     window_resolution: (f32, f32),
     mouse_position: (f32, f32),
@@ -81,37 +190,147 @@ impl ShaderPlayground {
     pub fn new(args: ShaderPlaygroundArgs) -> anyhow::Result<Self> {
         let plane = PlaneBuffer::new().with_context(|| "creating plane buffer")?;
 
+        let presenter = Presenter::new().with_context(|| "creating presenter")?;
+
+        let overlay = TextOverlay::new().with_context(|| "creating text overlay")?;
+
+        if !args.background_compile {
+            debug!("compiling shaders in the foreground");
+        } else {
+            match shader::enable_parallel_compile() {
+                Some(ParallelCompile::Driver) => debug!("compiling shaders in the background"),
+                Some(ParallelCompile::Worker) => debug!("compiling shaders on a worker thread"),
+                None => debug!("compiling shaders in the foreground, no shared context"),
+            }
+        }
+
         let watcher =
             FileWatcher::new(args.debouncer_ms).with_context(|| "creating a file watcher")?;
 
         let mut this = Self {
             quit: false,
             shader: Default::default(),
+            shader_path: None,
+            source_hash: None,
             plane,
             uniforms: Default::default(),
+            scene: Scene::new(args.procedural, args.depth_test, args.cull),
+            stage_paths: args.stages,
+            stages: Vec::new(),
+            stage_metadata: Vec::new(),
+            orbiting: false,
+
+            render_scale: args.render_scale,
+            scale_filter: args.scale_filter,
+            fixed_resolution: args.fixed_resolution,
+            render_target: None,
+            presenter,
+            overlay,
+
+            inspector: Default::default(),
+            diagnostics: None,
+            debug_printf: None,
+            profiler: None,
+            uses_profile: false,
+            pending: None,
+            defines: args.defines,
+            manifest: Vec::new(),
+            glsl: args.glsl.or(shader::is_gles().then_some(Dialect::Es300)),
+            variants: Variants::default(),
+            compare: None,
+            compute: None,
+            program_cache: if args.program_cache {
+                ProgramCache::new(ProgramCache::default_dir())
+            } else {
+                ProgramCache::disabled()
+            },
+            wgpu: None,
+            wgsl: false,
 
             watcher,
 
-            time_root: std::time::Instant::now(),
+            screenshot_requested: false,
+
+            started: std::time::Instant::now(),
+            clock: None,
+            time_root: 0.0,
 
             window_resolution: (0.0, 0.0),
             mouse_position: (0.0, 0.0),
             time: 0.0,
         };
 
+        if let Some(source) = args.compare {
+            let compare = Compare::new(source, args.scale_filter)
+                .with_context(|| "creating the comparison view")?;
+            if let Some(path) = compare.path() {
+                let _ = this.watcher.watch(path);
+            }
+            this.compare = Some(compare);
+        }
+
+        if !args.compute.is_empty() {
+            this.compute = Some(ComputePasses::new(args.compute));
+        }
+
+        if let Some(path) = &args.mesh {
+            if let Err(err) = this.scene.load_mesh(path) {
+                error!("could not load mesh. Error: {:#}", err);
+            }
+        }
+
         if let Some(path) = &args.file {
-            this.watch_file(&path);
-            this.load_shader(&path);
+            this.watch_file(path);
+            this.load_shader(path);
         } else {
-            info!("No file has been provided. Please re-run the program with a file, or drag and drop one onto the window.")
+            info!("No file has been provided. Please re-run the program with a file, or drag and drop one onto the window.");
+            this.reload_shader();
+        }
+
+        // the passes are compiled with the shader's variants, so after it is
+        // loaded, unless that already did
+        if this
+            .compute
+            .as_ref()
+            .is_some_and(|compute| compute.defines().is_none())
+        {
+            this.reload_compute();
         }
 
+        // the clock starts once the playground is ready
+        this.started = std::time::Instant::now();
+        this.time_root = 0.0;
+
         Ok(this)
     }
 
     fn watch_file(&mut self, path: &Path) {
         let _ = self.watcher.unwatch_all();
 
+        // a manifest created later is read when the shader is reloaded
+        let manifest_path = Some(manifest::path(path)).filter(|path| path.exists());
+        let other_paths = [
+            self.compare.as_ref().and_then(Compare::path),
+            self.scene.mesh_path.as_deref(),
+            manifest_path.as_deref(),
+        ];
+        let stage_paths = self.stage_paths.iter().map(|(_, path)| path);
+        let compute_paths = self.compute.iter().flat_map(|compute| &compute.paths);
+        let other_paths = other_paths
+            .into_iter()
+            .flatten()
+            .chain(stage_paths.chain(compute_paths).map(PathBuf::as_path))
+            .collect::<Vec<_>>();
+        for other_path in other_paths {
+            if let Err(err) = self.watcher.watch(other_path) {
+                error!(
+                    "could not watch file `{}`. Error: {}",
+                    other_path.display(),
+                    err
+                );
+            }
+        }
+
         match self.watcher.watch(path) {
             Ok(_) => {
                 debug!("successfully watching `{}`", path.display())
@@ -122,10 +341,171 @@ impl ShaderPlayground {
         }
     }
 
+    /// Starts compiling the shader at `path`. The current shader keeps
+    /// rendering until the new one is done, see [`Self::poll_pending_shader`].
     fn load_shader(&mut self, path: &Path) {
-        self.time_root = std::time::Instant::now();
+        if renderer::is_wgsl(path) {
+            self.load_wgsl(path);
+            return;
+        }
+        self.wgsl = false;
 
-        self.shader = ReloadedShader::from_file(path);
+        let source = std::fs::read_to_string(path);
+        self.shader_path = Some(path.to_path_buf());
+        let source_hash = source.as_deref().ok().map(screenshot::source_hash);
+        let stages = self.update_stages();
+        // a newer version supersedes a shader that is still compiling
+        self.pending = None;
+        let preprocessed = source.map(|source| {
+            self.manifest = manifest::load_defines(path)?;
+            self.variants.update(&source)?;
+            preprocessor::preprocess(&normalize::normalize(&source, Stage::Fragment, self.glsl))
+        });
+        match (stages, preprocessed) {
+            (Err(err), _) | (_, Err(err)) => self.swap_shader(
+                ReloadedShader::FileReadingError(err),
+                source_hash,
+                Vec::new(),
+                false,
+            ),
+            (_, Ok(Err(err))) => self.swap_shader(
+                ReloadedShader::ShaderError(err),
+                source_hash,
+                Vec::new(),
+                false,
+            ),
+            (Ok(()), Ok(Ok(preprocessed))) => {
+                let defines = self.variants.defines(&self.defines());
+                if self
+                    .compute
+                    .as_ref()
+                    .is_some_and(|compute| compute.defines() != Some(&defines))
+                {
+                    self.reload_compute();
+                }
+                let stages = self
+                    .stages
+                    .iter()
+                    .map(|(stage, source)| {
+                        (*stage, variants::inject_after_version(source, &defines))
+                    })
+                    .collect::<Vec<_>>();
+                self.pending = Some(PendingShader {
+                    program: ReloadedShader::start(
+                        &stages,
+                        &variants::inject_after_version(&preprocessed.source, &defines),
+                        &mut self.program_cache,
+                    ),
+                    source_hash,
+                    printf_formats: preprocessed.printf_formats,
+                    uses_profile: preprocessed.uses_profile,
+                });
+                self.poll_pending_shader();
+            }
+        }
+    }
+
+    /// Loads a WGSL shader into the wgpu renderer, creating it on first use.
+    /// WGSL shaders are drawn on the full screen quad, without the other
+    /// stages, compute passes, variants or the playground's GLSL extensions.
+    fn load_wgsl(&mut self, path: &Path) {
+        self.shader_path = Some(path.to_path_buf());
+        self.pending = None;
+        self.shader = ReloadedShader::default();
+        self.debug_printf = None;
+        self.uses_profile = false;
+        self.wgsl = true;
+        self.update_render_target();
+
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                error!("could not load file. Error: {}", err);
+                return;
+            }
+        };
+        self.source_hash = Some(screenshot::source_hash(&source));
+
+        if self.wgpu.is_none() {
+            match WgpuRenderer::new() {
+                Ok(renderer) => self.wgpu = Some(Box::new(renderer)),
+                Err(err) => {
+                    error!("could not create the wgpu renderer. Error: {:#}", err);
+                    return;
+                }
+            }
+        }
+        let Some(renderer) = &mut self.wgpu else {
+            return;
+        };
+        match renderer.load(&source) {
+            Ok(()) => {
+                info!("shader successfully loadded");
+                self.time_root = self.now();
+            }
+            Err(err) => error!("shader compilation error: {}", err),
+        }
+    }
+
+    /// Renders the current WGSL shader and uploads the frame into the render target.
+    ///
+    /// This is the renderer's headless path reused as a prototype: every frame
+    /// is read back from wgpu and uploaded again into a gl texture, a round
+    /// trip from the GPU to the CPU and back that limits the frame rate at high
+    /// resolutions. Presenting through a wgpu surface would avoid it, but the
+    /// window and its overlays are drawn with gl.
+    fn draw_wgsl(&mut self, resolution: (u32, u32), mouse_position: (f32, f32)) {
+        let (Some(renderer), Some(target)) = (&mut self.wgpu, &self.render_target) else {
+            return;
+        };
+        let inputs = FrameInputs {
+            resolution,
+            time: self.time,
+            mouse: (mouse_position.0, resolution.1 as f32 - mouse_position.1),
+        };
+        match renderer.render_image(inputs) {
+            Ok(image) => target.upload(&image),
+            Err(err) => error!("could not render the WGSL shader. Error: {:#}", err),
+        }
+    }
+
+    /// Seconds since the playground started.
+    fn now(&self) -> f32 {
+        self.clock
+            .unwrap_or_else(|| self.started.elapsed().as_secs_f32())
+    }
+
+    /// Swaps in the pending shader once the driver is done with it.
+    fn poll_pending_shader(&mut self) {
+        if !self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.program.is_ready())
+        {
+            return;
+        }
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let shader = ReloadedShader::finish(pending.program, &mut self.program_cache);
+        self.swap_shader(
+            shader,
+            pending.source_hash,
+            pending.printf_formats,
+            pending.uses_profile,
+        );
+    }
+
+    fn swap_shader(
+        &mut self,
+        shader: ReloadedShader,
+        source_hash: Option<String>,
+        printf_formats: Vec<String>,
+        uses_profile: bool,
+    ) {
+        self.time_root = self.now();
+        self.shader = shader;
+        self.source_hash = source_hash;
         match &self.shader {
             ReloadedShader::NotProvided => error!("unexpected state: `NotProvided`."),
             ReloadedShader::Shader(_) => info!("shader successfully loadded"),
@@ -133,12 +513,318 @@ impl ShaderPlayground {
             ReloadedShader::ShaderError(err) => error!("shader compilation error: {}", err),
         };
 
+        self.scene.tessellated = self
+            .stages
+            .iter()
+            .any(|(stage, _)| *stage == Stage::TessEvaluation);
+
         self.uniforms = self
             .shader
             .as_shader()
-            .map(|shader| Uniforms::init(shader))
+            .map(Uniforms::init)
             .unwrap_or_default();
+
+        self.debug_printf = self
+            .shader
+            .as_shader()
+            .and_then(|shader| DebugPrintf::new(shader, printf_formats));
+        // e.g. on ES, where the storage block cannot be bound from here
+        if let (None, Some(shader)) = (&self.debug_printf, self.shader.as_shader()) {
+            shader.use_program();
+            DebugPrintf::disable(shader);
+        }
+
+        self.uses_profile =
+            uses_profile && self.shader.as_shader().is_some_and(Profiler::bind_block);
+
+        if let (Some(compute), Some(shader)) = (&self.compute, self.shader.as_shader()) {
+            compute.bind_to(shader);
+        }
+
+        // the other shader shares the stages, and a revision follows the main shader's path
+        let defines = self.variants.defines(&self.defines());
+        if let Some(compare) = &mut self.compare {
+            compare.load(
+                self.shader_path.as_deref(),
+                &self.stages,
+                &defines,
+                self.glsl,
+                &mut self.program_cache,
+            );
+        }
+    }
+
+    /// Re-reads the user's stages, adding the mesh vertex shader if a mesh is
+    /// loaded and no vertex shader was given. Clears the stages on error.
+    fn update_stages(&mut self) -> std::io::Result<()> {
+        let stages = self
+            .stage_paths
+            .iter()
+            .map(|(stage, path)| {
+                let source = std::fs::read_to_string(path)?;
+                let metadata = StageMetadata {
+                    stage: stage.to_string(),
+                    path: path.clone(),
+                    source_sha256: screenshot::source_hash(&source),
+                };
+                let normalized = normalize::normalize(&source, *stage, self.glsl);
+                Ok(((*stage, normalized), metadata))
+            })
+            .collect::<std::io::Result<Vec<_>>>();
+        let result = stages.map(|stages| {
+            (self.stages, self.stage_metadata) = stages.into_iter().unzip();
+        });
+        if result.is_err() {
+            self.stages.clear();
+            self.stage_metadata.clear();
+        }
+
+        let has = |stages: &[(Stage, String)], stage| stages.iter().any(|(s, _)| *s == stage);
+        if self.scene.mesh.is_some() && !has(&self.stages, Stage::Vertex) {
+            self.stages
+                .push((Stage::Vertex, VERTEX_SHADER_MESH.to_string()));
+        }
+        result
+    }
+
+    fn load_mesh(&mut self, path: &Path) {
+        match self.scene.load_mesh(path) {
+            Ok(()) => info!("mesh `{}` successfully loaded", path.display()),
+            Err(err) => error!("could not load mesh. Error: {:#}", err),
+        }
+        self.update_render_target();
+        self.reload_shader();
+    }
+
+    /// The `-D` options followed by the manifest's defines they do not override.
+    fn defines(&self) -> Vec<(String, String)> {
+        manifest::merge(&self.defines, &self.manifest)
+    }
+
+    /// Recompiles the current shader, e.g. after one of the other stages changed.
+    fn reload_shader(&mut self) {
+        if let Some(shader_path) = self.shader_path.clone() {
+            self.load_shader(&shader_path);
+        } else {
+            if let Err(err) = self.update_stages() {
+                error!("could not load file. Error: {}", err);
+            }
+            self.reload_compare();
+        }
+    }
+
+    fn reload_compare(&mut self) {
+        let defines = self.variants.defines(&self.defines());
+        if let Some(compare) = &mut self.compare {
+            compare.load(
+                self.shader_path.as_deref(),
+                &self.stages,
+                &defines,
+                self.glsl,
+                &mut self.program_cache,
+            );
+        }
+    }
+
+    /// Recompiles the compute passes and points the current shader at their resources.
+    fn reload_compute(&mut self) {
+        let resolution = self.render_resolution();
+        let defines = self.variants.defines(&self.defines());
+        if let Some(compute) = &mut self.compute {
+            compute.load(resolution, &defines, &mut self.program_cache);
+            if let Some(shader) = self.shader.as_shader() {
+                compute.bind_to(shader);
+            }
+        }
+    }
+
+    /// Moves the wipe of the comparison to the mouse.
+    fn update_wipe(&mut self) {
+        let (x, _) = self.window_to_render(self.mouse_position);
+        let (width, _) = self.render_resolution();
+        if let Some(compare) = &mut self.compare {
+            compare.wipe = (x / width as f32).clamp(0.0, 1.0);
+        }
+    }
+
+    /// The resolution the shader is rendered at, after applying the render scale.
+    fn render_resolution(&self) -> (u32, u32) {
+        if let Some(resolution) = self.fixed_resolution {
+            return resolution;
+        }
+        (
+            ((self.window_resolution.0 * self.render_scale).round() as u32).max(1),
+            ((self.window_resolution.1 * self.render_scale).round() as u32).max(1),
+        )
+    }
+
+    /// The region of the window (`x`, `y` from the bottom left, `width`, `height`)
+    /// the rendered image is presented in. In fixed resolution mode the image
+    /// keeps its aspect ratio and the rest of the window is left black.
+    fn viewport(&self) -> (i32, i32, i32, i32) {
+        let (window_width, window_height) = self.window_resolution;
+        match self.fixed_resolution {
+            Some((width, height)) => {
+                let scale = (window_width / width as f32).min(window_height / height as f32);
+                let width = (width as f32 * scale).round();
+                let height = (height as f32 * scale).round();
+                (
+                    ((window_width - width) * 0.5).floor() as _,
+                    ((window_height - height) * 0.5).floor() as _,
+                    width as _,
+                    height as _,
+                )
+            }
+            None => (0, 0, window_width as _, window_height as _),
+        }
+    }
+
+    /// Maps a position in window pixels (measured from the top left) to
+    /// the render resolution (also measured from the top left).
+    fn window_to_render(&self, position: (f32, f32)) -> (f32, f32) {
+        let (x, y, width, height) = self.viewport();
+        let top = self.window_resolution.1 - (y + height) as f32;
+        let (render_width, render_height) = self.render_resolution();
+        (
+            (position.0 - x as f32) * render_width as f32 / width.max(1) as f32,
+            (position.1 - top) * render_height as f32 / height.max(1) as f32,
+        )
+    }
+
+    /// Reads back the last rendered frame and saves it along with its metadata.
+    fn take_screenshot(&self, mouse_position: (f32, f32)) {
+        let (width, height) = self.render_resolution();
+        let image = match &self.render_target {
+            Some(target) => Image::read_framebuffer(target.fbo, target.width, target.height),
+            None => Image::read_framebuffer(render_target::default_framebuffer(), width, height),
+        };
+
+        let metadata = ScreenshotMetadata {
+            shader: self.shader_path.clone(),
+            source_sha256: self.source_hash.clone(),
+            time: self.time,
+            mouse: (mouse_position.0, height as f32 - mouse_position.1),
+            resolution: (width, height),
+            scene: self.scene.is_3d().then(|| SceneMetadata {
+                mesh: self.scene.mesh_path.clone(),
+                camera: (&self.scene.camera).into(),
+                depth_test: self.scene.depth_test,
+                cull: self.scene.cull,
+            }),
+            stages: self.stage_metadata.clone(),
+            glsl: self.glsl,
+            defines: self
+                .defines()
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect(),
+            variants: self
+                .variants
+                .selected()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        };
+
+        match screenshot::save(&image, &metadata) {
+            Ok(path) => info!("saved screenshot `{}`", path.display()),
+            Err(err) => error!("could not save screenshot. Error: {:#}", err),
+        }
+    }
+
+    /// The render target pixel under the mouse, measured from the bottom left.
+    fn hovered_pixel(&self) -> Option<(u32, u32)> {
+        let (x, y) = self.window_to_render(self.mouse_position);
+        let (width, height) = self.render_resolution();
+        let y = height as f32 - y;
+        if x < 0.0 || y < 0.0 || x >= width as f32 || y >= height as f32 {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    fn update_render_target(&mut self) {
+        let needs_float_target = self.inspector.enabled || self.diagnostics.is_some();
+        let needs_target = self.fixed_resolution.is_some()
+            || self.render_scale != 1.0
+            || needs_float_target
+            || self.profiler.is_some()
+            || self.compare.is_some()
+            || self.wgsl
+            // the default framebuffer may not have a depth buffer
+            || self.scene.is_3d();
+        if !needs_target {
+            self.render_target = None;
+            return;
+        }
+
+        // the inspector and the diagnostic view need the unclamped values written by the shader
+        let format = if needs_float_target {
+            Format::Rgba32F
+        } else {
+            Format::Rgba8
+        };
+        if let Some(target) = &self.render_target {
+            if target.format != format {
+                self.render_target = None;
+            }
+        }
+
+        let (width, height) = self.render_resolution();
+        let result = match &mut self.render_target {
+            Some(target) => target.resize(width, height),
+            None => RenderTarget::new(width, height, format, self.scale_filter)
+                .map(|target| self.render_target = Some(target)),
+        };
+        if let Err(err) = result {
+            error!("could not create the render target. Error: {}", err);
+            self.render_target = None;
+        }
+    }
+
+    fn toggle_diagnostics(&mut self) {
+        if self.diagnostics.take().is_some() {
+            info!("diagnostic view disabled");
+        } else {
+            match Diagnostics::new() {
+                Ok(diagnostics) => {
+                    info!("diagnostic view enabled");
+                    self.diagnostics = Some(diagnostics);
+                    self.profiler = None;
+                }
+                Err(err) => error!("could not enable the diagnostic view. Error: {}", err),
+            }
+        }
+        self.update_render_target();
     }
+
+    fn toggle_profiler(&mut self) {
+        if self.profiler.take().is_some() {
+            info!("profile heatmap disabled");
+        } else {
+            match Profiler::new() {
+                Ok(profiler) => {
+                    info!("profile heatmap enabled");
+                    self.profiler = Some(profiler);
+                    self.diagnostics = None;
+                }
+                Err(err) => error!("could not enable the profile heatmap. Error: {}", err),
+            }
+        }
+        self.update_render_target();
+    }
+}
+
+/// Whether the file extension is one of a mesh format instead of a shader.
+fn is_mesh_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        ["obj", "gltf", "glb"]
+            .iter()
+            .any(|mesh| extension.eq_ignore_ascii_case(mesh))
+    })
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
 }
 
 impl App for ShaderPlayground {
@@ -149,55 +835,341 @@ impl App for ShaderPlayground {
     fn on_resize(&mut self, width: u32, height: u32) {
         debug!("on_resize event: ({}, {})", width, height);
         self.window_resolution = (width as _, height as _);
-        unsafe {
-            crate::gl::Viewport(0, 0, width as i32, height as i32);
+        self.update_render_target();
+    }
+
+    fn handle_mouse_input(&mut self, button: MouseButton, state: ElementState) {
+        if self.inspector.enabled {
+            if state != ElementState::Pressed {
+                return;
+            }
+            match button {
+                MouseButton::Left => self.inspector.pin(self.hovered_pixel()),
+                MouseButton::Right => self.inspector.pin(None),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(compare) = &mut self.compare {
+            if compare.mode == CompareMode::Wipe && button == MouseButton::Left {
+                compare.dragging = state == ElementState::Pressed;
+                if compare.dragging {
+                    self.update_wipe();
+                }
+                return;
+            }
+        }
+
+        if self.scene.is_3d() && button == MouseButton::Left {
+            self.orbiting = state == ElementState::Pressed;
         }
     }
 
-    fn handle_mouse_input(&mut self, _button: MouseButton, _state: ElementState) {}
+    fn handle_mouse_wheel_input(&mut self, delta: f32) {
+        if self.scene.is_3d() {
+            self.scene.camera.zoom(delta);
+        }
+    }
 
     fn handle_mouse_motion_input(&mut self, mouse_position: (f32, f32)) {
+        if self.orbiting {
+            self.scene.camera.orbit((
+                mouse_position.0 - self.mouse_position.0,
+                mouse_position.1 - self.mouse_position.1,
+            ));
+        }
         self.mouse_position = mouse_position;
+        if self
+            .compare
+            .as_ref()
+            .is_some_and(|compare| compare.dragging)
+        {
+            self.update_wipe();
+        }
     }
 
     fn handle_file_drop_input(&mut self, path: PathBuf) {
+        if is_mesh_file(&path) {
+            info!("loading mesh `{}`", path.display());
+            self.load_mesh(&path);
+            if let Some(shader_path) = self.shader_path.clone() {
+                self.watch_file(&shader_path);
+            }
+            return;
+        }
+
         info!("loading and watching `{}`", path.display());
         self.load_shader(&path);
         self.watch_file(&path);
     }
 
-    fn handle_key_input(&mut self, event: winit::event::KeyEvent) {
+    fn handle_key_input(&mut self, event: KeyInput) {
+        use winit::keyboard::{KeyCode, PhysicalKey};
+
+        if event.state != ElementState::Pressed || event.repeat {
+            return;
+        }
+
         match event.physical_key {
-            winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::Escape) => {
+            PhysicalKey::Code(KeyCode::Escape) => {
                 debug!("escape pressed - quitting");
                 self.quit = true
             }
+            PhysicalKey::Code(KeyCode::F12) => {
+                debug!("F12 pressed - taking a screenshot");
+                self.screenshot_requested = true;
+            }
+            PhysicalKey::Code(KeyCode::KeyI) => {
+                self.inspector.toggle();
+                self.update_render_target();
+            }
+            PhysicalKey::Code(KeyCode::KeyL) => self.inspector.toggle_loupe(),
+            PhysicalKey::Code(KeyCode::KeyN) => self.toggle_diagnostics(),
+            PhysicalKey::Code(KeyCode::KeyP) => self.toggle_profiler(),
+            PhysicalKey::Code(KeyCode::KeyR) => {
+                if let Some(compute) = &mut self.compute {
+                    compute.reset();
+                }
+            }
+            PhysicalKey::Code(KeyCode::Tab) => self.variants.select_next(),
+            PhysicalKey::Code(KeyCode::KeyV) => {
+                if self.variants.is_current_overridden(&self.defines()) {
+                    warn!("the variant is overridden by a `-D` option or the manifest");
+                } else if let Some(variant) = self.variants.cycle() {
+                    info!("variant: {}", variant);
+                    self.reload_shader();
+                }
+            }
+            PhysicalKey::Code(KeyCode::KeyC) => {
+                if let Some(compare) = &mut self.compare {
+                    compare.mode = compare.mode.next();
+                    compare.dragging = false;
+                    info!("comparison mode: {:?}", compare.mode);
+                }
+            }
+            PhysicalKey::Code(KeyCode::BracketLeft) => {
+                if let Some(compare) = &mut self.compare {
+                    compare.scale_gain(0.5);
+                }
+            }
+            PhysicalKey::Code(KeyCode::BracketRight) => {
+                if let Some(compare) = &mut self.compare {
+                    compare.scale_gain(2.0);
+                }
+            }
             _ => {}
         }
     }
 
     fn draw(&mut self) {
         if let Some(path) = self.watcher.file_changed() {
-            self.load_shader(&path);
+            let is = |other: Option<&Path>| other.is_some_and(|other| same_file(other, &path));
+            if is(self.compare.as_ref().and_then(Compare::path)) {
+                self.reload_compare();
+            } else if self
+                .compute
+                .as_ref()
+                .is_some_and(|compute| compute.contains(&path))
+            {
+                self.reload_compute();
+            } else if self
+                .stage_paths
+                .iter()
+                .any(|(_, stage_path)| is(Some(stage_path)))
+                || is(self.shader_path.as_deref().map(manifest::path).as_deref())
+            {
+                self.reload_shader();
+            } else if is(self.scene.mesh_path.as_deref()) {
+                self.load_mesh(&path);
+            } else {
+                self.load_shader(&path);
+            }
         }
+        self.poll_pending_shader();
 
-        self.time = self.time_root.elapsed().as_secs_f32();
+        self.time = self.now() - self.time_root;
+
+        let (width, height) = self.render_resolution();
+        let render_resolution = (width as f32, height as f32);
+        let mouse_position = self.window_to_render(self.mouse_position);
+
+        if let Some(compute) = &mut self.compute {
+            compute.run((width, height), mouse_position, self.time);
+        }
 
         unsafe {
+            match &self.render_target {
+                Some(target) => target.bind(),
+                None => gl::Viewport(0, 0, width as _, height as _),
+            }
+
             gl::ClearColor(0.0, 0.0, 0.0, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
+            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT);
 
-            match self.shader.as_shader() {
-                Some(shader) => {
-                    shader.use_program();
-                    self.uniforms
-                        .bind(self.window_resolution, self.mouse_position, self.time);
+            if let Some(shader) = self.shader.as_shader() {
+                shader.use_program();
+                self.uniforms
+                    .bind(render_resolution, mouse_position, self.time);
+                if let Some(debug_printf) = &self.debug_printf {
+                    debug_printf.bind(self.inspector.pinned().or(self.hovered_pixel()));
+                }
+                match &mut self.profiler {
+                    Some(profiler) if self.uses_profile => {
+                        profiler.begin((width, height));
+                        self.uniforms.bind_profile_width(width);
+                    }
+                    _ => self.uniforms.bind_profile_width(0),
+                }
 
-                    gl::BindVertexArray(self.plane.vao);
-                    gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+                if let Some(compute) = &self.compute {
+                    compute.bind_resources();
+                }
+                self.scene.draw(&self.plane, self.uniforms, (width, height));
+                if let Some(compute) = &self.compute {
+                    compute.unbind_resources();
                 }
-                _ => {}
             }
         }
+        if self.wgsl {
+            self.draw_wgsl((width, height), mouse_position);
+        }
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            self.take_screenshot(mouse_position);
+        }
+
+        let window_size = (self.window_resolution.0 as _, self.window_resolution.1 as _);
+
+        if let Some(target) = &self.render_target {
+            let pixel_info = if self.inspector.enabled {
+                self.inspector.inspect(target, self.hovered_pixel())
+            } else {
+                None
+            };
+
+            // the other shader receives exactly the same inputs
+            if let Some(compare) = &mut self.compare {
+                if let Err(err) =
+                    compare.render(target, &self.plane, &self.scene, mouse_position, self.time)
+                {
+                    error!("could not render the comparison shader. Error: {}", err);
+                }
+            }
+
+            let viewport = self.viewport();
+            let comparing = self.diagnostics.is_none()
+                && !(self.profiler.is_some() && self.uses_profile)
+                && self.compare.is_some();
+            let presented = match (&mut self.diagnostics, &mut self.profiler) {
+                (Some(diagnostics), _) => {
+                    match diagnostics.run(target, &self.plane, self.scale_filter) {
+                        Ok(diagnosed) => diagnosed,
+                        Err(err) => {
+                            error!("could not run the diagnostic view. Error: {}", err);
+                            target
+                        }
+                    }
+                }
+                (None, Some(profiler)) if self.uses_profile => {
+                    match profiler.run((width, height), &self.plane, self.scale_filter) {
+                        Ok(heatmap) => heatmap,
+                        Err(err) => {
+                            error!("could not render the profile heatmap. Error: {}", err);
+                            target
+                        }
+                    }
+                }
+                _ => target,
+            };
+
+            unsafe {
+                RenderTarget::unbind();
+                gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            match &mut self.compare {
+                Some(compare) if comparing => {
+                    if let Err(err) =
+                        compare.present(target, &self.plane, &self.presenter, viewport)
+                    {
+                        error!("could not present the comparison. Error: {}", err);
+                    }
+                    if let Some(label) = compare.label() {
+                        self.overlay.draw(
+                            &self.plane,
+                            window_size,
+                            Anchor::BottomRight,
+                            OVERLAY_MARGIN,
+                            &[label],
+                        );
+                    }
+                }
+                _ => self.presenter.present(presented, &self.plane, viewport),
+            }
+
+            if let Some(diagnostics) = &self.diagnostics {
+                diagnostics.draw_counts(&self.plane, &self.overlay, window_size);
+            }
+            if let Some(profiler) = &self.profiler {
+                if self.uses_profile {
+                    profiler.draw_legend(&self.plane, &self.overlay, window_size);
+                } else {
+                    self.overlay.draw(
+                        &self.plane,
+                        window_size,
+                        Anchor::TopRight,
+                        OVERLAY_MARGIN,
+                        &["the shader does not call PROFILE_COUNT()"],
+                    );
+                }
+            }
+            match pixel_info {
+                Some(info) => self.inspector.draw(
+                    &info,
+                    target,
+                    &self.plane,
+                    &self.presenter,
+                    &self.overlay,
+                    window_size,
+                ),
+                None if self.inspector.enabled => {
+                    self.inspector
+                        .draw_hint(&self.plane, &self.overlay, window_size)
+                }
+                None => {}
+            }
+        }
+
+        if let Some(debug_printf) = &mut self.debug_printf {
+            let lines = debug_printf.read();
+            self.overlay.draw(
+                &self.plane,
+                window_size,
+                Anchor::BottomLeft,
+                OVERLAY_MARGIN,
+                lines,
+            );
+        }
+
+        // the inspector uses the same corner
+        if !self.inspector.enabled {
+            let mut lines = self.variants.lines();
+            if self.pending.is_some() {
+                lines.push("compiling...".to_string());
+            }
+            self.overlay.draw(
+                &self.plane,
+                window_size,
+                Anchor::TopLeft,
+                OVERLAY_MARGIN,
+                &lines,
+            );
+        }
+    }
+
+    fn set_time(&mut self, time: f32) {
+        self.clock = Some(time);
     }
 }
@@ -0,0 +1,448 @@
+use log::warn;
+use serde::Serialize;
+
+use crate::shader::Stage;
+
+use super::preprocessor::find_call;
+
+/// A GLSL version shaders can be translated to, serialized like `--glsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Dialect {
+    /// `#version 330 core`
+    #[serde(rename = "330")]
+    Glsl330,
+    /// `#version 450 core`
+    #[serde(rename = "450")]
+    Glsl450,
+    /// `#version 300 es`
+    #[serde(rename = "300es")]
+    Es300,
+}
+
+impl Dialect {
+    fn version_line(self) -> &'static str {
+        match self {
+            Self::Glsl330 => "#version 330 core",
+            Self::Glsl450 => "#version 450 core",
+            Self::Es300 => "#version 300 es",
+        }
+    }
+
+    /// Whether `layout(binding = N)` is available.
+    fn has_binding_layout(self) -> bool {
+        self == Self::Glsl450
+    }
+}
+
+/// The `#version` a shader declares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Version {
+    number: u32,
+    es: bool,
+}
+
+impl Version {
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.trim().strip_prefix("#version")?.split_whitespace();
+        let number = words.next()?.parse().ok()?;
+        Some(Self {
+            number,
+            es: words.next() == Some("es"),
+        })
+    }
+
+    fn dialect(self) -> Option<Dialect> {
+        match (self.number, self.es) {
+            (300, true) => Some(Dialect::Es300),
+            (330, false) => Some(Dialect::Glsl330),
+            (450, false) => Some(Dialect::Glsl450),
+            _ => None,
+        }
+    }
+}
+
+/// The declarations added to fragment shaders that use these names without
+/// declaring them.
+const FRAGMENT_DECLARATIONS: [(&str, &str); 2] =
+    [("iUv", "in vec2 iUv;"), ("oColor", "out vec4 oColor;")];
+
+/// The playground's uniforms, declared for any stage that uses them.
+const UNIFORM_DECLARATIONS: [(&str, &str); 8] = [
+    ("uResolution", "uniform vec2 uResolution;"),
+    ("uMouse", "uniform vec2 uMouse;"),
+    ("uTime", "uniform float uTime;"),
+    ("uModel", "uniform mat4 uModel;"),
+    ("uView", "uniform mat4 uView;"),
+    ("uProjection", "uniform mat4 uProjection;"),
+    ("uCameraPosition", "uniform vec3 uCameraPosition;"),
+    ("uVertexCount", "uniform float uVertexCount;"),
+];
+
+/// Makes a shader that only contains its logic complete, and translates it
+/// to `target`:
+/// - The `#version` line is added, or replaced when translating. Without a
+///   `target`, ES shaders are translated to GLSL 330 core and other versions
+///   are kept.
+/// - The playground's uniforms, and `iUv` and `oColor` in fragment shaders,
+///   are declared if the shader uses them without declaring them.
+/// - `gl_FragColor` and `texture2D` are replaced by `oColor` and `texture`.
+/// - ES shaders get a default float and int precision, and `binding` layout
+///   qualifiers are removed for versions without them.
+///
+/// A `#line` directive keeps compiler errors pointing at the user's lines.
+pub fn normalize(source: &str, stage: Stage, target: Option<Dialect>) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let version = lines
+        .iter()
+        .position(|line| line.trim().starts_with("#version"));
+    let declared = version.and_then(|index| Version::parse(lines[index]));
+    let declared_dialect = declared.and_then(Version::dialect);
+    let target = match (target, declared) {
+        (Some(target), _) => Some(target),
+        (None, Some(declared)) if !declared.es => declared_dialect,
+        (None, _) => Some(Dialect::Glsl330),
+    };
+    // an unknown desktop version is kept as it is
+    let version_line = match target {
+        Some(target) => target.version_line(),
+        None => version.map_or("", |index| lines[index].trim()),
+    };
+
+    // the directives and comments at the top stay in front of the declarations
+    let header_len = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            !(line.is_empty()
+                || line.starts_with("//")
+                || line.starts_with("#version")
+                || line.starts_with("#extension"))
+        })
+        .unwrap_or(lines.len());
+    let body = lines[header_len..].join("\n");
+    let mut body = translate(&body, stage, target, declared_dialect != target);
+
+    let mut code = String::new();
+    if target == Some(Dialect::Es300) && !declares_precision(&body) {
+        code.push_str("precision highp float;\nprecision highp int;\n");
+    }
+    let fragment_declarations = match stage {
+        Stage::Fragment => &FRAGMENT_DECLARATIONS[..],
+        _ => &[],
+    };
+    for (name, declaration) in fragment_declarations.iter().chain(&UNIFORM_DECLARATIONS) {
+        if find_call(&body, name).is_some() && !declares(&body, name) {
+            // a shader writing to another output keeps it
+            if *name == "oColor" && declares_output(&body) {
+                continue;
+            }
+            code.push_str(declaration);
+            code.push('\n');
+        }
+    }
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+
+    let mut out = String::with_capacity(source.len() + code.len() + 64);
+    out.push_str(version_line);
+    out.push('\n');
+    for (index, line) in lines[..header_len].iter().enumerate() {
+        if Some(index) != version {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out.push_str(&code);
+    out.push_str(&format!("#line {}\n", header_len + 1));
+    out.push_str(&body);
+    out
+}
+
+/// Rewrites the idioms of other GLSL versions for `target`. Qualifiers the
+/// target does not have are only removed if the shader was written for
+/// another version.
+fn translate(body: &str, stage: Stage, target: Option<Dialect>, translating: bool) -> String {
+    let mut body = body.to_string();
+    if stage == Stage::Fragment {
+        body = replace_identifier(&body, "gl_FragColor", "oColor");
+    }
+    body = replace_identifier(&body, "texture2D", "texture");
+    if translating && target.is_some_and(|target| !target.has_binding_layout()) {
+        body = remove_binding_layouts(&body);
+    }
+    body
+}
+
+fn replace_identifier(source: &str, from: &str, to: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(offset) = find_call(rest, from) {
+        out.push_str(&rest[..offset]);
+        out.push_str(to);
+        rest = &rest[offset + from.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Removes `binding = N` from layout qualifiers, and the qualifier itself if
+/// nothing else is left. Samplers then have to be bound from the host.
+fn remove_binding_layouts(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(offset) = find_call(rest, "layout") {
+        let after = &rest[offset + "layout".len()..];
+        let Some((open, close)) = after
+            .find('(')
+            .filter(|&open| after[..open].trim().is_empty())
+            .and_then(|open| Some((open, open + after[open..].find(')')?)))
+        else {
+            out.push_str(&rest[..offset + "layout".len()]);
+            rest = after;
+            continue;
+        };
+
+        let qualifiers = after[open + 1..close]
+            .split(',')
+            .filter(|qualifier| {
+                qualifier
+                    .split('=')
+                    .next()
+                    .is_none_or(|name| name.trim() != "binding")
+            })
+            .collect::<Vec<_>>();
+        out.push_str(&rest[..offset]);
+        if qualifiers.len() < after[open + 1..close].split(',').count() {
+            warn!("removing a `binding` layout qualifier, which this GLSL version does not have");
+        }
+        if !qualifiers.is_empty() {
+            out.push_str(&format!("layout({})", qualifiers.join(",")));
+        }
+        rest = &after[close + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The statements of `source` outside of comments, split at `;`, `{` and `}`.
+fn statements(source: &str) -> Vec<String> {
+    let mut code = String::with_capacity(source.len());
+    let mut rest = source;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("//") {
+            rest = &comment[comment.find('\n').unwrap_or(comment.len())..];
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            code.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    code.split([';', '{', '}'])
+        .map(|statement| statement.trim().to_string())
+        .collect()
+}
+
+/// The storage qualifier of a global declaration, skipping layout,
+/// interpolation and precision qualifiers.
+fn storage_qualifier(statement: &str) -> Option<&str> {
+    let mut rest = statement.trim_start();
+    if let Some(layout) = rest.strip_prefix("layout") {
+        rest = &layout[layout.find(')')? + 1..];
+    }
+    rest.split_whitespace().find(|word| {
+        !matches!(
+            *word,
+            "flat" | "smooth" | "noperspective" | "centroid" | "highp" | "mediump" | "lowp"
+        )
+    })
+}
+
+fn declares(source: &str, name: &str) -> bool {
+    statements(source).iter().any(|statement| {
+        matches!(storage_qualifier(statement), Some("in" | "out" | "uniform"))
+            && find_call(statement, name).is_some()
+    })
+}
+
+fn declares_output(source: &str) -> bool {
+    statements(source)
+        .iter()
+        .any(|statement| storage_qualifier(statement) == Some("out"))
+}
+
+fn declares_precision(source: &str) -> bool {
+    statements(source).iter().any(|statement| {
+        statement.starts_with("precision") && find_call(statement, "float").is_some()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOGIC: &str = "void main() { gl_FragColor = texture2D(uTex, iUv) * uTime; }";
+
+    #[test]
+    fn completes_a_shader_without_version() {
+        assert_eq!(
+            normalize(LOGIC, Stage::Fragment, None),
+            "#version 330 core\n\
+             in vec2 iUv;\n\
+             out vec4 oColor;\n\
+             uniform float uTime;\n\
+             #line 1\n\
+             void main() { oColor = texture(uTex, iUv) * uTime; }\n"
+        );
+    }
+
+    #[test]
+    fn writes_the_version_of_every_dialect() {
+        let first_line = |dialect| {
+            normalize(LOGIC, Stage::Fragment, Some(dialect))
+                .lines()
+                .next()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(first_line(Dialect::Glsl330), "#version 330 core");
+        assert_eq!(first_line(Dialect::Glsl450), "#version 450 core");
+        assert_eq!(first_line(Dialect::Es300), "#version 300 es");
+    }
+
+    #[test]
+    fn adds_a_default_precision_for_es() {
+        let es = normalize(LOGIC, Stage::Fragment, Some(Dialect::Es300));
+        assert!(es.contains("\nprecision highp float;\nprecision highp int;\n"));
+        for dialect in [Dialect::Glsl330, Dialect::Glsl450] {
+            assert!(!normalize(LOGIC, Stage::Fragment, Some(dialect)).contains("precision"));
+        }
+    }
+
+    #[test]
+    fn keeps_a_declared_precision() {
+        let source = "#version 300 es\nprecision mediump float;\nout vec4 color;\nvoid main() { color = vec4(1.0); }";
+        assert_eq!(
+            normalize(source, Stage::Fragment, Some(Dialect::Es300)),
+            "#version 300 es\n\
+             #line 2\n\
+             precision mediump float;\n\
+             out vec4 color;\n\
+             void main() { color = vec4(1.0); }\n"
+        );
+    }
+
+    #[test]
+    fn keeps_or_replaces_the_declared_version() {
+        let source = "#version 410 core\nvoid main() {}\n";
+        assert_eq!(
+            normalize(source, Stage::Fragment, None),
+            "#version 410 core\n#line 2\nvoid main() {}\n"
+        );
+        assert_eq!(
+            normalize(source, Stage::Fragment, Some(Dialect::Glsl450)),
+            "#version 450 core\n#line 2\nvoid main() {}\n"
+        );
+        // without a target ES shaders run on desktop GL
+        let es = "#version 300 es\nprecision highp float;\nvoid main() {}\n";
+        assert_eq!(
+            normalize(es, Stage::Fragment, None),
+            "#version 330 core\n#line 2\nprecision highp float;\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_header_in_front_of_the_declarations() {
+        let source = "// a gradient\n\n#version 330 core\n#extension GL_ARB_foo : enable\nvoid main() { oColor = vec4(iUv, 0.0, 1.0); }";
+        assert_eq!(
+            normalize(source, Stage::Fragment, None),
+            "#version 330 core\n\
+             // a gradient\n\
+             \n\
+             #extension GL_ARB_foo : enable\n\
+             in vec2 iUv;\n\
+             out vec4 oColor;\n\
+             #line 5\n\
+             void main() { oColor = vec4(iUv, 0.0, 1.0); }\n"
+        );
+    }
+
+    #[test]
+    fn keeps_existing_declarations() {
+        let source = "uniform float uTime;\nlayout(location = 0) out vec4 color;\nvoid main() { color = vec4(uTime); }";
+        let normalized = normalize(source, Stage::Fragment, None);
+        assert_eq!(normalized.matches("uTime;").count(), 1);
+        assert!(!normalized.contains("oColor"));
+    }
+
+    #[test]
+    fn declares_uniforms_for_other_stages_only() {
+        let source =
+            "in vec3 aPosition;\nvoid main() { gl_Position = uProjection * vec4(aPosition, 1.0); }";
+        assert_eq!(
+            normalize(source, Stage::Vertex, None),
+            "#version 330 core\n\
+             uniform mat4 uProjection;\n\
+             #line 1\n\
+             in vec3 aPosition;\n\
+             void main() { gl_Position = uProjection * vec4(aPosition, 1.0); }\n"
+        );
+    }
+
+    #[test]
+    fn rewrites_identifiers_only() {
+        let body = "// gl_FragColor = texture2D(a, b);\n\
+                    /* texture2D */\n\
+                    vec4 my_texture2D = texture2DLod(a, b, 0.0);\n\
+                    gl_FragColor = texture2D (a, b);\n";
+        assert_eq!(
+            translate(body, Stage::Fragment, Some(Dialect::Glsl330), false),
+            "// gl_FragColor = texture2D(a, b);\n\
+             /* texture2D */\n\
+             vec4 my_texture2D = texture2DLod(a, b, 0.0);\n\
+             oColor = texture (a, b);\n"
+        );
+    }
+
+    #[test]
+    fn keeps_gl_frag_color_in_other_stages() {
+        let body = "gl_FragColor = texture2D(a, b);";
+        assert_eq!(
+            translate(body, Stage::Vertex, Some(Dialect::Glsl330), false),
+            "gl_FragColor = texture(a, b);"
+        );
+    }
+
+    #[test]
+    fn removes_binding_layouts_only_when_translating() {
+        let body = "layout(binding = 0) uniform sampler2D uTex;";
+        assert_eq!(
+            translate(body, Stage::Fragment, Some(Dialect::Glsl330), true),
+            " uniform sampler2D uTex;"
+        );
+        assert_eq!(
+            translate(body, Stage::Fragment, Some(Dialect::Glsl330), false),
+            body
+        );
+        assert_eq!(
+            translate(body, Stage::Fragment, Some(Dialect::Glsl450), true),
+            body
+        );
+    }
+
+    #[test]
+    fn removes_the_binding_qualifier_only() {
+        assert_eq!(
+            remove_binding_layouts(
+                "layout(std140, binding=1) uniform Block { float x; };\n\
+                 layout (binding = 2) uniform sampler2D a; // layout(binding = 3)\n\
+                 layout(location = 0) out vec4 color;"
+            ),
+            "layout(std140) uniform Block { float x; };\n \
+             uniform sampler2D a; // layout(binding = 3)\n\
+             layout(location = 0) out vec4 color;"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::{
+    gl,
+    image::Image,
+    plane_buffer::PlaneBuffer,
+    render_target::{Filter, Format, RenderTarget},
+    shader::{self, ShaderProgram, Stage},
+};
+
+use super::{debug_printf::DebugPrintf, normalize, preprocessor, uniforms::Uniforms};
+
+const VERTEX_SHADER: &str = include_str!("../res/basic_shaders/vert.glsl");
+
+/// The inputs of a single frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameInputs {
+    pub resolution: (u32, u32),
+    pub time: f32,
+    /// The value of `uMouse`, measured from the bottom left.
+    pub mouse: (f32, f32),
+}
+
+/// A fragment shader compiled for rendering outside of the playground window.
+pub struct OffscreenShader {
+    program: ShaderProgram,
+    uniforms: Uniforms,
+}
+
+impl OffscreenShader {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("reading `{}`", path.display()))?;
+        Self::from_source(&source).with_context(|| format!("compiling `{}`", path.display()))
+    }
+
+    pub fn from_source(source: &str) -> shader::Result<Self> {
+        let preprocessed = preprocessor::preprocess(&normalize::normalize(
+            source,
+            Stage::Fragment,
+            shader::is_gles().then_some(normalize::Dialect::Es300),
+        ))?;
+        let program = ShaderProgram::new(VERTEX_SHADER, &preprocessed.source)?;
+        let uniforms = Uniforms::init(&program);
+
+        program.use_program();
+        DebugPrintf::disable(&program);
+        uniforms.bind_profile_width(0);
+
+        Ok(Self { program, uniforms })
+    }
+
+    /// Draws one frame into `target`, which must match `inputs.resolution`.
+    pub fn draw(&self, target: &RenderTarget, plane: &PlaneBuffer, inputs: FrameInputs) {
+        let (width, height) = inputs.resolution;
+        target.bind();
+        self.program.use_program();
+        // `Uniforms::bind` expects the mouse measured from the top left
+        self.uniforms.bind(
+            (width as f32, height as f32),
+            (inputs.mouse.0, height as f32 - inputs.mouse.1),
+            inputs.time,
+        );
+        unsafe {
+            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+
+            gl::BindVertexArray(plane.vao);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+        }
+        RenderTarget::unbind();
+    }
+
+    /// Renders one frame and reads it back.
+    pub fn render_image(&self, plane: &PlaneBuffer, inputs: FrameInputs) -> anyhow::Result<Image> {
+        let (width, height) = inputs.resolution;
+        let target = RenderTarget::new(width, height, Format::Rgba8, Filter::Nearest)?;
+        self.draw(&target, plane, inputs);
+        Ok(Image::read_framebuffer(target.fbo, width, height))
+    }
+}
@@ -0,0 +1,461 @@
+use crate::shader::Error;
+
+/// The name of the storage block `debugPrintf` writes to. Its binding is set
+/// from the host since `layout(binding)` is not available in GLSL 330.
+pub const DEBUG_PRINTF_BLOCK: &str = "_DebugPrintf";
+/// The capacity, in 32-bit words, of the `debugPrintf` storage buffer.
+pub const DEBUG_PRINTF_CAPACITY: usize = 4096;
+
+/// The name of the storage block `PROFILE_COUNT()` increments counters in.
+pub const PROFILE_BLOCK: &str = "_ProfileCounts";
+
+/// The type tags written in front of every `debugPrintf` argument. The upper
+/// bits of a tag hold the number of components.
+pub const TAG_FLOAT: u32 = 0;
+pub const TAG_INT: u32 = 1;
+pub const TAG_UINT: u32 = 2;
+pub const TAG_BOOL: u32 = 3;
+pub const TAG_COMPONENTS_SHIFT: u32 = 4;
+
+/// A fragment shader with the playground extensions expanded into plain GLSL.
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessed {
+    pub source: String,
+    /// The format string of every `debugPrintf` call, indexed by call id.
+    pub printf_formats: Vec<String>,
+    /// Whether the shader calls `PROFILE_COUNT()`.
+    pub uses_profile: bool,
+}
+
+/// Expands the playground's extensions to GLSL:
+/// - `debugPrintf("x = %f", x);` writes its arguments into a storage buffer
+///   for the pixel at `uDebugPixel` only.
+/// - `PROFILE_COUNT()` increments a per-pixel counter while `uProfileWidth`
+///   (the width of the counter grid) is non-zero.
+pub fn preprocess(source: &str) -> Result<Preprocessed, Error> {
+    let mut out = String::with_capacity(source.len());
+    let mut printf_formats = Vec::new();
+
+    let mut rest = source;
+    while let Some(offset) = find_call(rest, "debugPrintf") {
+        out.push_str(&rest[..offset]);
+        let line = line_number(source, source.len() - rest.len() + offset);
+        rest = &rest[offset + "debugPrintf".len()..];
+
+        let (args, consumed) = parse_call_args(rest, "debugPrintf")
+            .map_err(|err| Error::PreprocessingError(format!("line {}: {}", line, err)))?;
+        let call = &rest[..consumed];
+        rest = &rest[consumed..];
+
+        let (format, args) = args.split_first().ok_or_else(|| {
+            Error::PreprocessingError(format!(
+                "line {}: `debugPrintf` needs a format string",
+                line
+            ))
+        })?;
+        let format = parse_string_literal(format).ok_or_else(|| {
+            Error::PreprocessingError(format!(
+                "line {}: the first argument of `debugPrintf` must be a string literal",
+                line
+            ))
+        })?;
+
+        let id = printf_formats.len();
+        printf_formats.push(format);
+
+        out.push_str(&format!(
+            "if (_dbgActive()) {{ _dbgPush({}u); _dbgPush({}u);",
+            id,
+            args.len()
+        ));
+        for arg in args {
+            out.push_str(&format!(" _dbgWrite({});", arg.trim()));
+        }
+        out.push_str(" }");
+
+        // keep the line numbers of the following code intact
+        out.extend(std::iter::repeat_n('\n', call.matches('\n').count()));
+    }
+    out.push_str(rest);
+
+    let uses_profile = find_call(&out, "PROFILE_COUNT").is_some();
+
+    let mut library = String::new();
+    if !printf_formats.is_empty() {
+        library.push_str(&debug_printf_library());
+    }
+    if uses_profile {
+        library.push_str(&profile_library());
+    }
+
+    let source = if library.is_empty() {
+        out
+    } else {
+        inject_after_directives(
+            &out,
+            "#extension GL_ARB_shader_storage_buffer_object : require",
+            &library,
+        )
+    };
+
+    Ok(Preprocessed {
+        source,
+        printf_formats,
+        uses_profile,
+    })
+}
+
+/// Removes the playground's extensions for compilers other than the driver's:
+/// `debugPrintf` and `PROFILE_COUNT()` calls are dropped, keeping the line
+/// numbers intact.
+pub fn strip(source: &str) -> Result<String, Error> {
+    let mut out = source.to_string();
+    for name in ["debugPrintf", "PROFILE_COUNT"] {
+        let mut stripped = String::with_capacity(out.len());
+        let mut rest = out.as_str();
+        while let Some(offset) = find_call(rest, name) {
+            stripped.push_str(&rest[..offset]);
+            let line = line_number(&out, out.len() - rest.len() + offset);
+            rest = &rest[offset + name.len()..];
+
+            let (_, consumed) = parse_call_args(rest, name)
+                .map_err(|err| Error::PreprocessingError(format!("line {}: {}", line, err)))?;
+            stripped.extend(std::iter::repeat_n(
+                '\n',
+                rest[..consumed].matches('\n').count(),
+            ));
+            rest = &rest[consumed..];
+        }
+        stripped.push_str(rest);
+        out = stripped;
+    }
+    Ok(out)
+}
+
+/// Inserts `extension` after the `#version` and `#extension` directives at
+/// the top of `source`, and `code` after the default precisions following
+/// them, then a `#line` directive so compiler errors still point at the
+/// user's lines.
+fn inject_after_directives(source: &str, extension: &str, code: &str) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let is_directive = |line: &str| {
+        line.is_empty()
+            || line.starts_with("//")
+            || line.starts_with("#version")
+            || line.starts_with("#extension")
+    };
+    let directives_len = lines
+        .iter()
+        .position(|line| !is_directive(line.trim()))
+        .unwrap_or(lines.len());
+    // the code may need the default precisions, which must follow the extensions
+    let header_len = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            !(is_directive(line) || line.starts_with("precision"))
+        })
+        .unwrap_or(lines.len());
+
+    let mut out = String::with_capacity(source.len() + code.len() + extension.len());
+    for line in &lines[..directives_len] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(extension);
+    out.push('\n');
+    for line in &lines[directives_len..header_len] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(code);
+    out.push_str(&format!("#line {}\n", header_len + 1));
+    for line in &lines[header_len..] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+fn debug_printf_library() -> String {
+    let mut code = format!(
+        "layout(std430) buffer {block} {{ uint _dbgCount; uint _dbgData[]; }};\n\
+         uniform ivec2 uDebugPixel;\n\
+         bool _dbgActive() {{ return ivec2(gl_FragCoord.xy) == uDebugPixel; }}\n\
+         void _dbgPush(uint word) {{ uint i = atomicAdd(_dbgCount, 1u); if (i < {capacity}u) {{ _dbgData[i] = word; }} }}\n",
+        block = DEBUG_PRINTF_BLOCK,
+        capacity = DEBUG_PRINTF_CAPACITY - 1,
+    );
+
+    let types = [
+        ("float", "vec", TAG_FLOAT, "floatBitsToUint"),
+        ("int", "ivec", TAG_INT, "uint"),
+        ("uint", "uvec", TAG_UINT, "uint"),
+        ("bool", "bvec", TAG_BOOL, "uint"),
+    ];
+    for (scalar, vector, tag, to_uint) in types {
+        code.push_str(&format!(
+            "void _dbgWrite({scalar} v) {{ _dbgPush({tag}u); _dbgPush({to_uint}(v)); }}\n",
+            tag = tag | (1 << TAG_COMPONENTS_SHIFT),
+        ));
+        for n in 2..=4 {
+            let mut body = format!("_dbgPush({}u);", tag | (n << TAG_COMPONENTS_SHIFT));
+            for c in ["x", "y", "z", "w"].iter().take(n as usize) {
+                body.push_str(&format!(" _dbgPush({}(v.{}));", to_uint, c));
+            }
+            code.push_str(&format!(
+                "void _dbgWrite({}{} v) {{ {} }}\n",
+                vector, n, body
+            ));
+        }
+    }
+
+    code
+}
+
+fn profile_library() -> String {
+    // the first two words hold the min and max counts, written by the heatmap pass
+    format!(
+        "layout(std430) buffer {block} {{ uint _profMin; uint _profMax; uint _profCounts[]; }};\n\
+         uniform int uProfileWidth;\n\
+         void _profileCount() {{ if (uProfileWidth > 0) {{ ivec2 p = ivec2(gl_FragCoord.xy); atomicAdd(_profCounts[p.y * uProfileWidth + p.x], 1u); }} }}\n\
+         #define PROFILE_COUNT() _profileCount()\n",
+        block = PROFILE_BLOCK,
+    )
+}
+
+/// Finds the next call to `name` outside of comments, returning its byte offset.
+pub(crate) fn find_call(source: &str, name: &str) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let name = name.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(b"//") {
+            i += rest.iter().position(|&c| c == b'\n').unwrap_or(rest.len());
+        } else if rest.starts_with(b"/*") {
+            i += rest
+                .windows(2)
+                .position(|w| w == b"*/")
+                .map(|end| end + 2)
+                .unwrap_or(rest.len());
+        } else if rest.starts_with(name)
+            && (i == 0 || !is_identifier_char(bytes[i - 1]))
+            && !rest
+                .get(name.len())
+                .copied()
+                .is_some_and(is_identifier_char)
+        {
+            return Some(i);
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Parses `(arg, arg, ...)` followed by an optional `;`, splitting on top level
+/// commas. Returns the arguments and the number of bytes consumed.
+fn parse_call_args<'a>(source: &'a str, name: &str) -> Result<(Vec<&'a str>, usize), String> {
+    let open = source
+        .find(|c: char| !c.is_whitespace())
+        .filter(|&i| source[i..].starts_with('('))
+        .ok_or_else(|| format!("expected `(` after `{}`", name))?;
+
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut arg_start = open + 1;
+    for (i, c) in source[open + 1..].char_indices() {
+        let i = open + 1 + i;
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' => depth += 1,
+            ')' | ']' if depth > 0 => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(&source[arg_start..i]);
+                arg_start = i + 1;
+            }
+            ')' => {
+                args.push(&source[arg_start..i]);
+                let mut end = i + 1;
+                let after = &source[end..];
+                if let Some(semicolon) = after.find(|c: char| !c.is_whitespace()) {
+                    if after[semicolon..].starts_with(';') {
+                        end += semicolon + 1;
+                    }
+                }
+                return Ok((args, end));
+            }
+            _ => {}
+        }
+    }
+
+    Err(format!("unterminated `{}` call", name))
+}
+
+fn parse_string_literal(literal: &str) -> Option<String> {
+    let literal = literal.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                c => out.push(c),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+fn is_identifier_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+fn line_number(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_top_level_arguments() {
+        let (args, consumed) = parse_call_args("(f(a, b), v[i, j], c) ;rest", "f").unwrap();
+        assert_eq!(args, ["f(a, b)", " v[i, j]", " c"]);
+        assert_eq!(consumed, "(f(a, b), v[i, j], c) ;".len());
+    }
+
+    #[test]
+    fn ignores_commas_and_parentheses_in_strings() {
+        let (args, _) = parse_call_args(r#"("a, (b)", x)"#, "f").unwrap();
+        assert_eq!(args, [r#""a, (b)""#, " x"]);
+
+        let (args, _) = parse_call_args(r#"("say \"hi, there\")", x)"#, "f").unwrap();
+        assert_eq!(args, [r#""say \"hi, there\")""#, " x"]);
+    }
+
+    #[test]
+    fn rejects_malformed_calls() {
+        assert_eq!(
+            parse_call_args(" x", "debugPrintf").unwrap_err(),
+            "expected `(` after `debugPrintf`"
+        );
+        assert_eq!(
+            parse_call_args("(a, (b)", "debugPrintf").unwrap_err(),
+            "unterminated `debugPrintf` call"
+        );
+        assert_eq!(
+            parse_call_args(r#"("a)"#, "debugPrintf").unwrap_err(),
+            "unterminated `debugPrintf` call"
+        );
+    }
+
+    #[test]
+    fn parses_string_literals() {
+        assert_eq!(
+            parse_string_literal(r#" "a\tb\n\"c\"\\" "#).as_deref(),
+            Some("a\tb\n\"c\"\\")
+        );
+        assert_eq!(parse_string_literal("x"), None);
+        assert_eq!(parse_string_literal(r#""a\"#), None);
+    }
+
+    #[test]
+    fn finds_calls_outside_comments_and_identifiers() {
+        let source = "// debugPrintf(a)\n/* debugPrintf(b) */ mydebugPrintf(c); debugPrintf(d);";
+        assert_eq!(
+            find_call(source, "debugPrintf"),
+            source.rfind("debugPrintf")
+        );
+        assert_eq!(find_call("debugPrintfs(a);", "debugPrintf"), None);
+    }
+
+    #[test]
+    fn injects_after_version_and_extensions() {
+        let source = "#version 330 core\n#extension GL_foo : enable\n\nvoid main() {}\n";
+        assert_eq!(
+            inject_after_directives(source, "#extension EXT : require", "code;\n"),
+            "#version 330 core\n#extension GL_foo : enable\n\n\
+             #extension EXT : require\ncode;\n#line 4\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn injects_code_after_precisions() {
+        let source = "#version 300 es\nprecision highp float;\nvoid main() {}\n";
+        assert_eq!(
+            inject_after_directives(source, "#extension EXT : require", "code;\n"),
+            "#version 300 es\n#extension EXT : require\nprecision highp float;\n\
+             code;\n#line 3\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn injects_without_version() {
+        assert_eq!(
+            inject_after_directives("void main() {}", "#extension EXT : require", "code;\n"),
+            "#extension EXT : require\ncode;\n#line 1\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn expands_debug_printf_keeping_lines() {
+        let source =
+            "#version 430\nvoid main() {\n    debugPrintf(\"%d, %f\",\n        i, f);\n    x;\n}\n";
+        let preprocessed = preprocess(source).unwrap();
+        assert_eq!(preprocessed.printf_formats, ["%d, %f"]);
+        assert!(!preprocessed.uses_profile);
+
+        let body = preprocessed.source.split("#line 2\n").nth(1).unwrap();
+        assert_eq!(
+            body,
+            "void main() {\n    if (_dbgActive()) { _dbgPush(0u); _dbgPush(2u); \
+             _dbgWrite(i); _dbgWrite(f); }\n\n    x;\n}\n"
+        );
+    }
+
+    #[test]
+    fn reports_bad_format_strings() {
+        let err = |source| match preprocess(source) {
+            Err(Error::PreprocessingError(message)) => message,
+            other => panic!("unexpected result: {:?}", other.map(|p| p.source)),
+        };
+        assert_eq!(
+            err("\ndebugPrintf(x);"),
+            "line 2: the first argument of `debugPrintf` must be a string literal"
+        );
+        assert_eq!(
+            err("debugPrintf(\"a\""),
+            "line 1: unterminated `debugPrintf` call"
+        );
+    }
+
+    #[test]
+    fn detects_profile_counts() {
+        let preprocessed = preprocess("void main() { PROFILE_COUNT(); }").unwrap();
+        assert!(preprocessed.uses_profile);
+        assert!(preprocessed.source.contains("#define PROFILE_COUNT()"));
+    }
+
+    #[test]
+    fn strips_extensions_keeping_lines() {
+        let source = "a;\ndebugPrintf(\"%d\",\n    i);\nPROFILE_COUNT();\nb;\n";
+        assert_eq!(strip(source).unwrap(), "a;\n\n\n\nb;\n");
+    }
+}
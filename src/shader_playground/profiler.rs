@@ -0,0 +1,145 @@
+use log::debug;
+
+use crate::{
+    gl,
+    overlay::{Anchor, TextOverlay},
+    plane_buffer::PlaneBuffer,
+    render_target::{Filter, Format, RenderTarget},
+    shader::ShaderProgram,
+    storage_buffer::StorageBuffer,
+};
+
+use super::preprocessor::PROFILE_BLOCK;
+
+const VERTEX_SHADER: &str = include_str!("../res/basic_shaders/vert.glsl");
+const FRAG_SHADER_HEATMAP: &str = include_str!("../res/basic_shaders/frag_heatmap.glsl");
+
+/// The storage buffer binding `PROFILE_COUNT()` writes to.
+const BINDING: u32 = 2;
+/// The min and max counts are stored in front of the counters.
+const HEADER_LEN: usize = 2;
+
+const MARGIN: (i32, i32) = (8, 8);
+const LEGEND_HEIGHT: i32 = 12;
+
+#[derive(Debug, Clone, Copy)]
+struct Locations {
+    width: i32,
+    range: i32,
+    legend: i32,
+}
+
+/// Renders the per-pixel counts of `PROFILE_COUNT()` calls as a heatmap.
+pub struct Profiler {
+    program: ShaderProgram,
+    locations: Locations,
+    buffer: Option<StorageBuffer>,
+    target: Option<RenderTarget>,
+    /// The min and max counts of the last frame.
+    range: (u32, u32),
+}
+
+impl Profiler {
+    pub fn new() -> anyhow::Result<Self> {
+        let program = ShaderProgram::new(VERTEX_SHADER, FRAG_SHADER_HEATMAP)?;
+        let locations = Locations {
+            width: program.uniform_location("uWidth"),
+            range: program.uniform_location("uRange"),
+            legend: program.uniform_location("uLegend"),
+        };
+        Ok(Self {
+            program,
+            locations,
+            buffer: None,
+            target: None,
+            range: (0, 0),
+        })
+    }
+
+    /// Binds `shader`'s counter block, which `PROFILE_COUNT()` increments
+    /// while `uProfileWidth` is non-zero.
+    pub fn bind_block(shader: &ShaderProgram) -> bool {
+        shader.storage_block_binding(PROFILE_BLOCK, BINDING)
+    }
+
+    /// Resets the counters for a frame of `resolution` pixels.
+    pub fn begin(&mut self, resolution: (u32, u32)) {
+        let len = HEADER_LEN + (resolution.0 * resolution.1) as usize;
+        let buffer = match self.buffer.take() {
+            Some(buffer) if buffer.len == len => buffer,
+            _ => StorageBuffer::new(len),
+        };
+        let buffer = self.buffer.insert(buffer);
+
+        buffer.clear();
+        buffer.write(0, &[u32::MAX, 0]);
+        buffer.bind(BINDING);
+    }
+
+    /// Renders the counts of the frame as a heatmap and returns the target
+    /// holding it, ready to be presented.
+    pub fn run(
+        &mut self,
+        resolution: (u32, u32),
+        plane: &PlaneBuffer,
+        filter: Filter,
+    ) -> anyhow::Result<&RenderTarget> {
+        let Some(buffer) = &self.buffer else {
+            anyhow::bail!("`Profiler::begin` was not called");
+        };
+
+        let target = match self.target.take() {
+            Some(mut target) => {
+                target.resize(resolution.0, resolution.1)?;
+                target
+            }
+            None => RenderTarget::new(resolution.0, resolution.1, Format::Rgba8, filter)?,
+        };
+        let target = &*self.target.insert(target);
+
+        target.bind();
+        self.program.use_program();
+        unsafe {
+            // make the counters written by the profiled shader visible
+            gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+
+            let l = self.locations;
+            gl::Uniform1i(l.width, resolution.0 as _);
+            gl::Uniform2f(l.range, self.range.0 as _, self.range.1 as _);
+            gl::Uniform1i(l.legend, gl::FALSE as _);
+
+            gl::BindVertexArray(plane.vao);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+        }
+        RenderTarget::unbind();
+
+        let range = buffer.read_range(0, HEADER_LEN);
+        let range = (range[0].min(range[1]), range[1]);
+        if range != self.range {
+            debug!("PROFILE_COUNT min: {}, max: {}", range.0, range.1);
+            self.range = range;
+        }
+
+        Ok(target)
+    }
+
+    /// Draws the min and max counts of the last frame above the color scale.
+    pub fn draw_legend(&self, plane: &PlaneBuffer, overlay: &TextOverlay, window_size: (u32, u32)) {
+        let lines = [format!(
+            "PROFILE_COUNT  min {:<8} max {:<8}",
+            self.range.0, self.range.1
+        )];
+        overlay.draw(plane, window_size, Anchor::TopRight, MARGIN, &lines);
+
+        let (width, height) = TextOverlay::measure(&lines);
+        let x = window_size.0 as i32 - width - MARGIN.0;
+        let y = window_size.1 as i32 - height - MARGIN.1 - LEGEND_HEIGHT;
+        self.program.use_program();
+        unsafe {
+            gl::Viewport(x, y, width, LEGEND_HEIGHT);
+            gl::Uniform1i(self.locations.legend, gl::TRUE as _);
+            gl::BindVertexArray(plane.vao);
+            gl::DrawArrays(gl::TRIANGLE_FAN, 0, 4);
+        }
+    }
+}
@@ -1,10 +1,9 @@
-use std::{ops::Deref, path::Path};
+use crate::{
+    program_cache::ProgramCache,
+    shader::{PendingProgram, ShaderProgram, ShaderProgramBuilder, Stage},
+};
 
-use crate::shader::ShaderProgram;
-
-const VERTEX_SHADER: &'static str = include_str!("../res/basic_shaders/vert.glsl");
-const FRAG_SHADER_SIMPLE: &'static str =
-    include_str!("../res/basic_shaders/frag_simple_2d_gradient.glsl");
+const VERTEX_SHADER: &str = include_str!("../res/basic_shaders/vert.glsl");
 
 #[derive(Default)]
 pub enum ReloadedShader {
@@ -24,21 +23,34 @@ impl ReloadedShader {
         }
     }
 
-    pub fn example_shader() -> Self {
-        Self::from_str(FRAG_SHADER_SIMPLE)
+    /// Links the fragment shader with the other `stages`. The built-in full
+    /// screen vertex shader is used unless `stages` has a vertex shader.
+    pub fn from_sources(
+        stages: &[(Stage, String)],
+        fragment_shader: &str,
+        cache: &mut ProgramCache,
+    ) -> Self {
+        Self::finish(Self::start(stages, fragment_shader, cache), cache)
     }
 
-    pub fn from_str(fragment_shader: &str) -> Self {
-        match ShaderProgram::new(VERTEX_SHADER, fragment_shader) {
-            Err(err) => Self::ShaderError(err),
-            Ok(shader) => Self::Shader(shader),
-        }
+    /// Like [`Self::from_sources`], but leaves the driver compiling. The
+    /// result is read with [`Self::finish`].
+    pub fn start(
+        stages: &[(Stage, String)],
+        fragment_shader: &str,
+        cache: &mut ProgramCache,
+    ) -> PendingProgram {
+        let builder = stages.iter().fold(
+            ShaderProgramBuilder::new().vertex(VERTEX_SHADER),
+            |builder, (stage, source)| builder.stage(*stage, source),
+        );
+        builder.fragment(fragment_shader).start_cached(cache)
     }
 
-    pub fn from_file(fragment_shader_path: &Path) -> Self {
-        match std::fs::read_to_string(fragment_shader_path) {
-            Err(err) => return Self::FileReadingError(err),
-            Ok(x) => Self::from_str(&x),
+    pub fn finish(pending: PendingProgram, cache: &mut ProgramCache) -> Self {
+        match pending.finish_cached(cache) {
+            Err(err) => Self::ShaderError(err),
+            Ok(shader) => Self::Shader(shader),
         }
     }
 }
@@ -0,0 +1,269 @@
+use std::path::{Path, PathBuf};
+
+use glam::{
+    camera::rh::{proj::opengl, view},
+    Mat4, Vec3,
+};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    compat,
+    gl::{self, types::GLenum},
+    mesh::{MeshBuffer, MeshData},
+    plane_buffer::PlaneBuffer,
+    shader,
+};
+
+use super::uniforms::Uniforms;
+
+/// The default vertex shader for meshes, passing world space `iPosition`,
+/// `iNormal` and `iUv` to the fragment shader.
+pub const VERTEX_SHADER_MESH: &str = include_str!("../res/basic_shaders/vert_mesh.glsl");
+
+const MIN_PITCH: f32 = -1.5;
+const MAX_PITCH: f32 = 1.5;
+/// Radians per pixel of mouse movement.
+const ORBIT_SPEED: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Points,
+    Lines,
+    LineStrip,
+    Triangles,
+}
+
+impl Primitive {
+    fn gl_enum(self) -> GLenum {
+        match self {
+            Self::Points => gl::POINTS,
+            Self::Lines => gl::LINES,
+            Self::LineStrip => gl::LINE_STRIP,
+            Self::Triangles => gl::TRIANGLES,
+        }
+    }
+}
+
+/// Draws `vertex_count` vertices without any vertex buffer. The vertex shader
+/// computes the positions from `gl_VertexID`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Procedural {
+    pub vertex_count: u32,
+    pub primitive: Primitive,
+    /// A fixed point size in pixels. If `None`, the vertex shader may write `gl_PointSize`.
+    pub point_size: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cull {
+    None,
+    Back,
+    Front,
+}
+
+/// A camera circling around `target`, controlled with the mouse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitCamera {
+    pub target: Vec3,
+    pub distance: f32,
+    /// Rotation around the y axis in radians.
+    pub yaw: f32,
+    /// Rotation above the xz plane in radians.
+    pub pitch: f32,
+    /// Vertical field of view in radians.
+    pub fov_y: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            target: Vec3::ZERO,
+            distance: 3.0,
+            yaw: 0.0,
+            pitch: 0.3,
+            fov_y: 45f32.to_radians(),
+        }
+    }
+}
+
+impl OrbitCamera {
+    pub fn eye(&self) -> Vec3 {
+        let direction = Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        self.target + direction * self.distance
+    }
+
+    pub fn view(&self) -> Mat4 {
+        view::look_at_mat4(self.eye(), self.target, Vec3::Y)
+    }
+
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        opengl::perspective(
+            self.fov_y,
+            aspect,
+            self.distance * 0.01,
+            self.distance * 100.0,
+        )
+    }
+
+    /// Rotates the camera by a mouse movement of `delta` pixels.
+    pub fn orbit(&mut self, delta: (f32, f32)) {
+        self.yaw -= delta.0 * ORBIT_SPEED;
+        self.pitch = (self.pitch + delta.1 * ORBIT_SPEED).clamp(MIN_PITCH, MAX_PITCH);
+    }
+
+    /// Moves the camera closer (positive `steps`) or further away.
+    pub fn zoom(&mut self, steps: f32) {
+        self.distance *= 0.9f32.powf(steps);
+    }
+
+    /// Points the camera at the center of the bounding box and moves it back
+    /// until the whole box is visible.
+    pub fn frame(&mut self, bounds: (Vec3, Vec3)) {
+        let (min, max) = bounds;
+        let radius = ((max - min).length() * 0.5).max(1e-3);
+        self.target = (min + max) * 0.5;
+        self.distance = radius / (self.fov_y * 0.5).sin() * 1.1;
+    }
+}
+
+/// What the shader is drawn onto: the full screen quad, a mesh or procedural
+/// vertices, the latter two seen through an orbit camera.
+pub struct Scene {
+    pub mesh: Option<MeshBuffer>,
+    pub mesh_path: Option<PathBuf>,
+    pub procedural: Option<Procedural>,
+    /// A vertex array without any attributes for procedural drawing.
+    empty_vao: u32,
+    pub camera: OrbitCamera,
+    pub depth_test: bool,
+    pub cull: Cull,
+    /// Whether the program has a tessellation evaluation shader, which
+    /// consumes patches instead of primitives.
+    pub tessellated: bool,
+}
+
+impl Scene {
+    pub fn new(procedural: Option<Procedural>, depth_test: bool, cull: Cull) -> Self {
+        if procedural.is_some_and(|procedural| procedural.point_size.is_some()) && shader::is_gles()
+        {
+            warn!("`--point-size` is ignored on OpenGL ES, write `gl_PointSize` in the vertex shader instead");
+        }
+
+        let mut empty_vao = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut empty_vao);
+        }
+
+        Self {
+            mesh: None,
+            mesh_path: None,
+            procedural,
+            empty_vao,
+            camera: Default::default(),
+            depth_test,
+            cull,
+            tessellated: false,
+        }
+    }
+
+    /// Loads a mesh and frames the camera around it. Replaces procedural drawing.
+    pub fn load_mesh(&mut self, path: &Path) -> anyhow::Result<()> {
+        let data = MeshData::load(path)?;
+        let mesh = MeshBuffer::new(&data);
+        self.camera.frame(mesh.bounds);
+        self.mesh = Some(mesh);
+        self.mesh_path = Some(path.to_path_buf());
+        self.procedural = None;
+        Ok(())
+    }
+
+    /// Whether anything other than the full screen quad is drawn, which needs
+    /// the camera and a depth buffer.
+    pub fn is_3d(&self) -> bool {
+        self.mesh.is_some() || self.procedural.is_some()
+    }
+
+    /// Draws the mesh, or the full screen quad if there is none, with the
+    /// shader currently in use. Expects the framebuffer to have a depth buffer
+    /// when drawing a mesh.
+    pub(super) fn draw(&self, plane: &PlaneBuffer, uniforms: Uniforms, resolution: (u32, u32)) {
+        let aspect = resolution.0 as f32 / resolution.1.max(1) as f32;
+        uniforms.bind_camera(
+            Mat4::IDENTITY,
+            self.camera.view(),
+            self.camera.projection(aspect),
+            self.camera.eye(),
+        );
+        uniforms.bind_vertex_count(self.procedural.map_or(0, |p| p.vertex_count));
+
+        if !self.is_3d() {
+            unsafe {
+                gl::BindVertexArray(plane.vao);
+                gl::DrawArrays(self.mode(gl::TRIANGLE_FAN, 4), 0, 4);
+            }
+            return;
+        }
+
+        unsafe {
+            if self.depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+            }
+            match self.cull {
+                Cull::None => {}
+                Cull::Back => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::BACK);
+                }
+                Cull::Front => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::FRONT);
+                }
+            }
+
+            match (&self.mesh, self.procedural) {
+                (Some(mesh), _) => mesh.draw(self.mode(gl::TRIANGLES, 3)),
+                (None, Some(procedural)) => {
+                    compat::set_point_size(procedural.point_size);
+                    gl::BindVertexArray(self.empty_vao);
+                    gl::DrawArrays(
+                        self.mode(procedural.primitive.gl_enum(), 3),
+                        0,
+                        procedural.vertex_count as _,
+                    );
+                    compat::reset_point_size();
+                }
+                (None, None) => unreachable!(),
+            }
+
+            gl::Disable(gl::DEPTH_TEST);
+            gl::Disable(gl::CULL_FACE);
+        }
+    }
+}
+
+impl Scene {
+    /// `mode`, or patches of `patch_vertices` if the program is tessellated.
+    /// The full screen quad is a single patch of 4 vertices in fan order.
+    fn mode(&self, mode: GLenum, patch_vertices: i32) -> GLenum {
+        if !self.tessellated {
+            return mode;
+        }
+        compat::patch_vertices(patch_vertices);
+        gl::PATCHES
+    }
+}
+
+impl Drop for Scene {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.empty_vao);
+        }
+    }
+}
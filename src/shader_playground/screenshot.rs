@@ -0,0 +1,130 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::image::Image;
+
+use super::{
+    normalize::Dialect,
+    scene::{Cull, OrbitCamera},
+};
+
+/// Everything needed to reproduce a frame.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenshotMetadata {
+    pub shader: Option<PathBuf>,
+    /// Hex encoded SHA-256 of the shader source the frame was rendered with.
+    pub source_sha256: Option<String>,
+    #[serde(rename = "uTime")]
+    pub time: f32,
+    #[serde(rename = "uMouse")]
+    pub mouse: (f32, f32),
+    #[serde(rename = "uResolution")]
+    pub resolution: (u32, u32),
+    /// Set when the shader is drawn onto a mesh or procedural vertices.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scene: Option<SceneMetadata>,
+    /// The `-D NAME=VALUE` options.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub defines: Vec<String>,
+    /// The selected option of every `@variant` of the shader.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub variants: BTreeMap<String, String>,
+    /// The GLSL version shaders were translated to, from `--glsl`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub glsl: Option<Dialect>,
+    /// The stages linked with the fragment shader, e.g. from `--vertex`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<StageMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StageMetadata {
+    pub stage: String,
+    pub path: PathBuf,
+    pub source_sha256: String,
+}
+
+/// The mesh the shader was drawn onto and the camera looking at it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SceneMetadata {
+    pub mesh: Option<PathBuf>,
+    pub camera: CameraMetadata,
+    pub depth_test: bool,
+    pub cull: Cull,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CameraMetadata {
+    pub target: [f32; 3],
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov_y: f32,
+}
+
+impl From<&OrbitCamera> for CameraMetadata {
+    fn from(camera: &OrbitCamera) -> Self {
+        Self {
+            target: camera.target.to_array(),
+            distance: camera.distance,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            fov_y: camera.fov_y,
+        }
+    }
+}
+
+pub fn source_hash(source: &str) -> String {
+    Sha256::digest(source.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Saves `image` as `<shader name>-<timestamp>.png` next to the shader (or in the
+/// working directory if there is none), along with a `.json` sidecar containing
+/// `metadata`. Returns the path of the PNG.
+pub fn save(image: &Image, metadata: &ScreenshotMetadata) -> anyhow::Result<PathBuf> {
+    let dir = metadata
+        .shader
+        .as_deref()
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let name = metadata
+        .shader
+        .as_deref()
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "screenshot".to_string());
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S%.3f");
+    let png_path = dir.join(format!("{}-{}.png", name, timestamp));
+    let json_path = png_path.with_extension("json");
+
+    let json = serde_json::to_value(metadata).with_context(|| "serializing metadata")?;
+    let text = json
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(key, value)| {
+            let text = match value {
+                serde_json::Value::String(text) => text.clone(),
+                value => value.to_string(),
+            };
+            (key.clone(), text)
+        })
+        .collect::<Vec<_>>();
+    image.save_png(&png_path, &text)?;
+
+    let json = serde_json::to_string_pretty(&json).with_context(|| "serializing metadata")?;
+    std::fs::write(&json_path, json)
+        .with_context(|| format!("writing `{}`", json_path.display()))?;
+
+    Ok(png_path)
+}
@@ -0,0 +1,95 @@
+use glam::{Mat4, Vec3};
+
+use crate::{gl, shader::ShaderProgram};
+
+/// The locations of the playground's uniforms in a program. Uniforms the
+/// program does not use are at `-1`, which gl ignores.
+#[derive(Debug, Clone, Copy)]
+pub struct Uniforms {
+    window_resolution: i32,
+    mouse_position: i32,
+    time: i32,
+    profile_width: i32,
+    model: i32,
+    view: i32,
+    projection: i32,
+    camera_position: i32,
+    vertex_count: i32,
+}
+
+impl Default for Uniforms {
+    fn default() -> Self {
+        Self {
+            window_resolution: -1,
+            mouse_position: -1,
+            time: -1,
+            profile_width: -1,
+            model: -1,
+            view: -1,
+            projection: -1,
+            camera_position: -1,
+            vertex_count: -1,
+        }
+    }
+}
+
+impl Uniforms {
+    pub fn init(shader: &ShaderProgram) -> Self {
+        Self {
+            window_resolution: shader.uniform_location("uResolution"),
+            mouse_position: shader.uniform_location("uMouse"),
+            time: shader.uniform_location("uTime"),
+            profile_width: shader.uniform_location("uProfileWidth"),
+            model: shader.uniform_location("uModel"),
+            view: shader.uniform_location("uView"),
+            projection: shader.uniform_location("uProjection"),
+            camera_position: shader.uniform_location("uCameraPosition"),
+            vertex_count: shader.uniform_location("uVertexCount"),
+        }
+    }
+
+    pub fn bind(self, window_resolution: (f32, f32), mouse_position: (f32, f32), time: f32) {
+        unsafe {
+            gl::Uniform2f(
+                self.window_resolution,
+                window_resolution.0,
+                window_resolution.1,
+            );
+
+            gl::Uniform2f(
+                self.mouse_position,
+                mouse_position.0,
+                window_resolution.1 - mouse_position.1,
+            );
+
+            gl::Uniform1f(self.time, time);
+        }
+    }
+
+    pub fn bind_camera(self, model: Mat4, view: Mat4, projection: Mat4, camera_position: Vec3) {
+        unsafe {
+            gl::UniformMatrix4fv(self.model, 1, gl::FALSE, model.as_ref().as_ptr());
+            gl::UniformMatrix4fv(self.view, 1, gl::FALSE, view.as_ref().as_ptr());
+            gl::UniformMatrix4fv(self.projection, 1, gl::FALSE, projection.as_ref().as_ptr());
+            gl::Uniform3f(
+                self.camera_position,
+                camera_position.x,
+                camera_position.y,
+                camera_position.z,
+            );
+        }
+    }
+
+    pub fn bind_vertex_count(self, vertex_count: u32) {
+        unsafe {
+            gl::Uniform1f(self.vertex_count, vertex_count as f32);
+        }
+    }
+
+    /// `PROFILE_COUNT()` only counts while the width is non-zero.
+    pub fn bind_profile_width(self, width: u32) {
+        unsafe {
+            gl::Uniform1i(self.profile_width, width as _);
+        }
+    }
+}
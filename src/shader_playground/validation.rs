@@ -0,0 +1,216 @@
+use std::{fmt::Write, path::Path};
+
+use anyhow::{anyhow, Context};
+use naga::{
+    front::{glsl, wgsl},
+    valid::{Capabilities, ModuleInfo, ValidationFlags, Validator},
+    Module, ShaderStage, Span,
+};
+
+use crate::{
+    renderer::{self, webgpu},
+    shader::{self, Stage},
+};
+
+use super::{
+    manifest,
+    normalize::{self, Dialect},
+    preprocessor,
+    variants::{self, Variants},
+};
+
+/// A fragment shader parsed and validated by naga, without a gl context.
+pub struct ValidShader {
+    pub module: Module,
+    pub info: ModuleInfo,
+}
+
+impl ValidShader {
+    /// The name of the fragment entry point, `main` for GLSL.
+    pub fn entry_point(&self) -> &str {
+        self.module
+            .entry_points
+            .iter()
+            .find(|entry_point| entry_point.stage == ShaderStage::Fragment)
+            .map_or("main", |entry_point| &entry_point.name)
+    }
+}
+
+/// An error with the code it points at.
+struct Diagnostic {
+    message: String,
+    labels: Vec<(Span, String)>,
+}
+
+/// Parses and validates the fragment shader at `path`, GLSL or WGSL. Errors
+/// point at the lines of the file, with the offending code underlined.
+///
+/// GLSL shaders are completed like in the playground and translated to GLSL
+/// 450, which is what naga reads, with the defines of their manifest. Their
+/// uniforms get the bindings the wgpu renderer uses, and `debugPrintf` and
+/// `PROFILE_COUNT()` are dropped.
+pub fn validate_file(path: &Path, defines: &[(String, String)]) -> anyhow::Result<ValidShader> {
+    let source =
+        std::fs::read_to_string(path).with_context(|| format!("reading `{}`", path.display()))?;
+    let name = path.display().to_string();
+
+    let (source, module) = if renderer::is_wgsl(path) {
+        // the uniforms are declared after the user's code
+        let source = webgpu::declare_uniforms(&source);
+        let module = wgsl::parse_str(&source).map_err(|err| {
+            vec![Diagnostic {
+                message: err.message().to_string(),
+                labels: err
+                    .labels()
+                    .map(|(span, label)| (span, label.to_string()))
+                    .collect(),
+            }]
+        });
+        (source, module)
+    } else {
+        let source = manifest::load_defines(path)
+            .and_then(|manifest| prepare_glsl(&source, &manifest::merge(defines, &manifest)))
+            .with_context(|| format!("preprocessing `{}`", name))?;
+        let module = glsl::Frontend::default()
+            .parse(&glsl::Options::from(ShaderStage::Fragment), &source)
+            .map_err(|err| {
+                err.errors
+                    .into_iter()
+                    .map(|error| Diagnostic {
+                        message: error.kind.to_string(),
+                        labels: vec![(error.meta, String::new())],
+                    })
+                    .collect()
+            });
+        (source, module)
+    };
+    let module = module.map_err(|diagnostics: Vec<Diagnostic>| {
+        let errors = diagnostics
+            .iter()
+            .map(|diagnostic| render(&name, &source, diagnostic))
+            .collect::<Vec<_>>();
+        anyhow!(errors.join("\n\n"))
+    })?;
+
+    let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|err| {
+            let mut message = err.as_inner().to_string();
+            let mut cause = std::error::Error::source(err.as_inner());
+            while let Some(err) = cause {
+                message.push_str(&format!(": {}", err));
+                cause = err.source();
+            }
+            let diagnostic = Diagnostic {
+                message,
+                labels: err.spans().cloned().collect(),
+            };
+            anyhow!(render(&name, &source, &diagnostic))
+        })?;
+
+    Ok(ValidShader { module, info })
+}
+
+fn prepare_glsl(source: &str, defines: &[(String, String)]) -> shader::Result<String> {
+    let mut variants = Variants::default();
+    variants.update(source)?;
+    let defines = variants.defines(defines);
+
+    let source = normalize::normalize(source, Stage::Fragment, Some(Dialect::Glsl450));
+    let source = preprocessor::strip(&source)?;
+    Ok(bind_uniforms(&variants::inject_after_version(
+        &source, &defines,
+    )))
+}
+
+/// Adds a `binding` to every uniform declared without one, which naga
+/// requires. The playground's uniforms get the bindings of the wgpu renderer,
+/// the others the following ones. Lines are kept intact.
+fn bind_uniforms(source: &str) -> String {
+    let mut next_binding = webgpu::UNIFORM_DECLARATIONS.len() as u32;
+    let mut out = String::with_capacity(source.len());
+    for line in source.lines() {
+        let code = line.trim_start();
+        let indent = &line[..line.len() - code.len()];
+        let declares_uniform = code.starts_with("uniform ")
+            || (code.starts_with("layout") && code.contains(") uniform "));
+        if !declares_uniform || code.contains("binding") {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+
+        let binding = webgpu::UNIFORM_DECLARATIONS
+            .iter()
+            .find(|(name, _, _)| preprocessor::find_call(code, name).is_some())
+            .map(|(_, binding, _)| *binding)
+            .unwrap_or_else(|| {
+                next_binding += 1;
+                next_binding - 1
+            });
+        match code.strip_prefix("layout") {
+            Some(layout) => {
+                let qualifiers = layout.trim_start().trim_start_matches('(');
+                out.push_str(&format!(
+                    "{}layout(binding = {}, {}",
+                    indent, binding, qualifiers
+                ));
+            }
+            None => out.push_str(&format!("{}layout(binding = {}) {}", indent, binding, code)),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The line of the user's file every line of `source` came from, following
+/// the `#line` directives added while preparing it.
+fn user_lines(source: &str) -> Vec<u32> {
+    let mut lines = Vec::new();
+    let mut next = 1;
+    for line in source.lines() {
+        lines.push(next);
+        next = line
+            .trim()
+            .strip_prefix("#line")
+            .and_then(|number| number.split_whitespace().next()?.parse().ok())
+            .unwrap_or(next + 1);
+    }
+    lines
+}
+
+/// Formats `diagnostic` like rustc, with the line numbers of the user's file.
+fn render(name: &str, source: &str, diagnostic: &Diagnostic) -> String {
+    let user_lines = user_lines(source);
+    let mut out = format!("error: {}\n", diagnostic.message);
+    for (span, label) in &diagnostic.labels {
+        if !span.is_defined() {
+            continue;
+        }
+        let location = span.location(source);
+        let index = location.line_number.saturating_sub(1) as usize;
+        let text = source.lines().nth(index).unwrap_or_default();
+        let line = user_lines
+            .get(index)
+            .copied()
+            .unwrap_or(location.line_number);
+        let column = location.line_position.max(1) as usize;
+        let underline = (location.length as usize)
+            .min(text.len().saturating_sub(column - 1))
+            .max(1);
+
+        let gutter = " ".repeat(line.to_string().len());
+        let _ = writeln!(out, "{}--> {}:{}:{}", gutter, name, line, column);
+        let _ = writeln!(out, "{} |", gutter);
+        let _ = writeln!(out, "{} | {}", line, text);
+        let _ = writeln!(
+            out,
+            "{} | {}{} {}",
+            gutter,
+            " ".repeat(column - 1),
+            "^".repeat(underline),
+            label
+        );
+    }
+    out.trim_end().to_string()
+}
@@ -0,0 +1,367 @@
+use crate::shader::Error;
+
+/// A switch declared in a shader, e.g. `// @variant QUALITY = LOW|MEDIUM|HIGH`
+/// or `// @variant SHADOWS` for a boolean.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub name: String,
+    /// Empty for a boolean variant.
+    pub options: Vec<String>,
+    pub selected: usize,
+}
+
+impl Variant {
+    fn len(&self) -> usize {
+        if self.options.is_empty() {
+            2
+        } else {
+            self.options.len()
+        }
+    }
+
+    /// The macros selecting the current option. Every option of an enum is
+    /// numbered as `<NAME>_<OPTION>`, so `#if QUALITY == QUALITY_HIGH` works,
+    /// while a boolean is `0` or `1`.
+    fn macros(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        let options = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| (format!("{}_{}", self.name, option), index.to_string()));
+        options.chain(std::iter::once((
+            self.name.clone(),
+            self.selected.to_string(),
+        )))
+    }
+
+    fn value(&self) -> &str {
+        match self.options.get(self.selected) {
+            Some(option) => option,
+            None if self.selected == 0 => "off",
+            None => "on",
+        }
+    }
+}
+
+/// The variants of the current shader and the one hotkeys act on.
+#[derive(Debug, Clone, Default)]
+pub struct Variants {
+    list: Vec<Variant>,
+    current: usize,
+}
+
+impl Variants {
+    /// Re-reads the declarations in `source`, keeping the selected option of
+    /// variants that still exist.
+    pub fn update(&mut self, source: &str) -> Result<(), Error> {
+        let mut list = parse_variants(source)?;
+        for variant in &mut list {
+            if let Some(old) = self.list.iter().find(|old| old.name == variant.name) {
+                variant.selected = match (old.options.get(old.selected), variant.options.is_empty())
+                {
+                    (Some(option), false) => variant
+                        .options
+                        .iter()
+                        .position(|o| o == option)
+                        .unwrap_or(0),
+                    (None, true) => old.selected,
+                    _ => 0,
+                };
+            }
+        }
+        self.list = list;
+        self.current = self.current.min(self.list.len().saturating_sub(1));
+        Ok(())
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.list.is_empty() {
+            self.current = (self.current + 1) % self.list.len();
+        }
+    }
+
+    /// Switches the current variant to its next option.
+    pub fn cycle(&mut self) -> Option<&Variant> {
+        let variant = self.list.get_mut(self.current)?;
+        variant.selected = (variant.selected + 1) % variant.len();
+        Some(variant)
+    }
+
+    /// The `#define`s for the `-D NAME=VALUE` options, followed by the ones
+    /// selecting the current options. A `-D` option overrides a variant
+    /// macro of the same name instead of defining it twice.
+    pub fn defines(&self, options: &[(String, String)]) -> String {
+        let overridden = |name: &str| options.iter().any(|(option, _)| option == name);
+        let variants = self
+            .list
+            .iter()
+            .flat_map(Variant::macros)
+            .filter(|(name, _)| !overridden(name));
+        options
+            .iter()
+            .cloned()
+            .chain(variants)
+            .map(|(name, value)| format!("#define {} {}\n", name, value))
+            .collect()
+    }
+
+    /// Whether the current variant is overridden by one of the `-D` options.
+    pub fn is_current_overridden(&self, options: &[(String, String)]) -> bool {
+        self.list
+            .get(self.current)
+            .is_some_and(|variant| options.iter().any(|(name, _)| *name == variant.name))
+    }
+
+    /// The name and selected option of every variant.
+    pub fn selected(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.list
+            .iter()
+            .map(|variant| (variant.name.as_str(), variant.value()))
+    }
+
+    /// One line per variant for the overlay, marking the current one.
+    pub fn lines(&self) -> Vec<String> {
+        self.list
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| {
+                let marker = if index == self.current { '>' } else { ' ' };
+                format!("{} {} = {}", marker, variant.name, variant.value())
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.name, self.value())
+    }
+}
+
+/// Inserts `code` after the `#version` line of `source`, followed by a
+/// `#line` directive so compiler errors still point at the user's lines.
+/// Without a `#version` line the code goes first.
+pub fn inject_after_version(source: &str, code: &str) -> String {
+    if code.is_empty() {
+        return source.to_string();
+    }
+    let lines = source.lines().collect::<Vec<_>>();
+    let version = lines
+        .iter()
+        .position(|line| line.trim().starts_with("#version"))
+        .map_or(0, |index| index + 1);
+
+    let mut out = String::with_capacity(source.len() + code.len());
+    for line in &lines[..version] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push_str(code);
+    out.push_str(&format!("#line {}\n", version + 1));
+    for line in &lines[version..] {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+fn parse_variants(source: &str) -> Result<Vec<Variant>, Error> {
+    let mut variants = Vec::<Variant>::new();
+    for (line, text) in source.lines().enumerate() {
+        let Some(declaration) = text
+            .trim()
+            .strip_prefix("//")
+            .map(str::trim)
+            .and_then(|directive| directive.strip_prefix("@variant"))
+        else {
+            continue;
+        };
+        let err =
+            |message: &str| Error::PreprocessingError(format!("line {}: {}", line + 1, message));
+
+        let (name, options) = match declaration.split_once('=') {
+            Some((name, options)) => (
+                name.trim(),
+                options
+                    .split('|')
+                    .map(|option| option.trim().to_string())
+                    .collect::<Vec<_>>(),
+            ),
+            None => (declaration.trim(), Vec::new()),
+        };
+        if !is_identifier(name) {
+            return Err(err(
+                "`@variant` needs a name, e.g. `// @variant QUALITY = LOW|HIGH`",
+            ));
+        }
+        if options.iter().any(|option| !is_identifier(option)) {
+            return Err(err(
+                "the options of a variant must be identifiers separated by `|`",
+            ));
+        }
+        if variants.iter().any(|variant| variant.name == name) {
+            return Err(err(&format!("the variant `{}` is declared twice", name)));
+        }
+        variants.push(Variant {
+            name: name.to_string(),
+            options,
+            selected: 0,
+        });
+    }
+    Ok(variants)
+}
+
+pub(super) fn is_identifier(s: &str) -> bool {
+    s.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+#version 330 core
+// @variant QUALITY = LOW|MEDIUM|HIGH
+//@variant SHADOWS
+void main() {}
+";
+
+    fn variants(source: &str) -> Variants {
+        let mut variants = Variants::default();
+        variants.update(source).unwrap();
+        variants
+    }
+
+    #[test]
+    fn parses_enums_and_booleans() {
+        let list = parse_variants(SOURCE).unwrap();
+        assert_eq!(
+            list,
+            vec![
+                Variant {
+                    name: "QUALITY".to_string(),
+                    options: vec!["LOW".to_string(), "MEDIUM".to_string(), "HIGH".to_string()],
+                    selected: 0,
+                },
+                Variant {
+                    name: "SHADOWS".to_string(),
+                    options: Vec::new(),
+                    selected: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_declarations() {
+        let message = |source| match parse_variants(source) {
+            Err(Error::PreprocessingError(message)) => message,
+            other => panic!("expected an error, got {:?}", other),
+        };
+        assert!(message("// @variant = A|B").starts_with("line 1:"));
+        assert!(message("\n// @variant MODE = A|2B").starts_with("line 2:"));
+        assert_eq!(
+            message("// @variant A\n// @variant B\n// @variant A = X|Y"),
+            "line 3: the variant `A` is declared twice"
+        );
+    }
+
+    #[test]
+    fn cycles_the_current_variant() {
+        let mut variants = variants(SOURCE);
+        let values = (0..3)
+            .map(|_| variants.cycle().unwrap().value().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["MEDIUM", "HIGH", "LOW"]);
+
+        variants.select_next();
+        assert_eq!(variants.cycle().unwrap().value(), "on");
+        assert_eq!(variants.cycle().unwrap().value(), "off");
+
+        variants.select_next();
+        assert_eq!(variants.cycle().unwrap().name, "QUALITY");
+    }
+
+    #[test]
+    fn cycles_nothing_without_variants() {
+        let mut variants = variants("void main() {}");
+        variants.select_next();
+        assert!(variants.cycle().is_none());
+        assert_eq!(variants.defines(&[]), "");
+    }
+
+    #[test]
+    fn update_keeps_the_selected_options() {
+        let mut variants = variants(SOURCE);
+        variants.cycle();
+        variants.cycle();
+        variants
+            .update("// @variant QUALITY = HIGH|LOW\n// @variant SHADOWS\n")
+            .unwrap();
+        assert_eq!(variants.lines(), ["> QUALITY = HIGH", "  SHADOWS = off"]);
+    }
+
+    #[test]
+    fn defines_options_then_variants() {
+        let mut variants = variants(SOURCE);
+        variants.cycle();
+        let options = [("SCALE".to_string(), "2.0".to_string())];
+        assert_eq!(
+            variants.defines(&options),
+            "#define SCALE 2.0\n\
+             #define QUALITY_LOW 0\n\
+             #define QUALITY_MEDIUM 1\n\
+             #define QUALITY_HIGH 2\n\
+             #define QUALITY 1\n\
+             #define SHADOWS 0\n"
+        );
+    }
+
+    #[test]
+    fn options_override_variants() {
+        let variants = variants(SOURCE);
+        let options = [
+            ("SHADOWS".to_string(), "1".to_string()),
+            ("QUALITY".to_string(), "QUALITY_HIGH".to_string()),
+        ];
+        let defines = variants.defines(&options);
+        assert_eq!(defines.matches("#define SHADOWS ").count(), 1);
+        assert_eq!(defines.matches("#define QUALITY ").count(), 1);
+        assert!(defines.contains("#define QUALITY QUALITY_HIGH\n"));
+        assert!(defines.contains("#define QUALITY_HIGH 2\n"));
+        assert!(variants.is_current_overridden(&options));
+        assert!(!variants.is_current_overridden(&options[..1]));
+    }
+
+    #[test]
+    fn injects_after_the_version() {
+        assert_eq!(
+            inject_after_version("#version 330\nvoid main() {}\n", "#define A 1\n"),
+            "#version 330\n#define A 1\n#line 2\nvoid main() {}\n"
+        );
+        assert_eq!(
+            inject_after_version(
+                "// header\n\n  #version 450\nvoid main() {}",
+                "#define A 1\n"
+            ),
+            "// header\n\n  #version 450\n#define A 1\n#line 4\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn injects_first_without_a_version() {
+        assert_eq!(
+            inject_after_version("void main() {}\n", "#define A 1\n"),
+            "#define A 1\n#line 1\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn injects_nothing_without_code() {
+        let source = "#version 330\nvoid main() {}";
+        assert_eq!(inject_after_version(source, ""), source);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::compat;
+use crate::gl;
+use crate::gl::types::*;
+
+/// A shader storage buffer of 32-bit words.
+pub struct StorageBuffer {
+    pub id: u32,
+    /// Size in 32-bit words.
+    pub len: usize,
+}
+
+impl StorageBuffer {
+    pub fn new(len: usize) -> Self {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, id);
+            gl::BufferData(
+                gl::SHADER_STORAGE_BUFFER,
+                (len * std::mem::size_of::<u32>()) as GLsizeiptr,
+                std::ptr::null(),
+                gl::DYNAMIC_READ,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+        let this = Self { id, len };
+        this.clear();
+        this
+    }
+
+    /// Sets every word to zero.
+    pub fn clear(&self) {
+        compat::clear_storage_buffer(self.id, self.len);
+    }
+
+    /// Binds the buffer to `layout(binding = index)`.
+    pub fn bind(&self, index: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, index, self.id);
+        }
+    }
+
+    /// Overwrites the words starting at `offset`.
+    pub fn write(&self, offset: usize, data: &[u32]) {
+        assert!(offset + data.len() <= self.len);
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                (offset * std::mem::size_of::<u32>()) as GLintptr,
+                std::mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr().cast(),
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+    }
+
+    /// Waits for all writes from shaders and reads back the whole buffer.
+    pub fn read(&self) -> Vec<u32> {
+        self.read_range(0, self.len)
+    }
+
+    /// Waits for all writes from shaders and reads back `len` words starting at `offset`.
+    pub fn read_range(&self, offset: usize, len: usize) -> Vec<u32> {
+        assert!(offset + len <= self.len);
+        let mut data = vec![0u32; len];
+        unsafe {
+            gl::MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            // mapping works on ES as well, unlike `glGetBufferSubData`
+            let mapped = gl::MapBufferRange(
+                gl::SHADER_STORAGE_BUFFER,
+                (offset * std::mem::size_of::<u32>()) as GLintptr,
+                (len * std::mem::size_of::<u32>()) as GLsizeiptr,
+                gl::MAP_READ_BIT,
+            );
+            if !mapped.is_null() {
+                std::ptr::copy_nonoverlapping(mapped.cast::<u32>(), data.as_mut_ptr(), len);
+                gl::UnmapBuffer(gl::SHADER_STORAGE_BUFFER);
+            }
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+        data
+    }
+}
+
+impl Drop for StorageBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}